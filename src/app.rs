@@ -1,59 +1,53 @@
 use crate::{
     conf::Config,
-    parser::{Playlist, Song},
+    key::Key,
+    library::{FileType, Library},
+    parser::{Playlist, Song, MAX_CAPO},
 };
-use lazy_static::lazy_static;
-use regex::Regex;
 use rust_music_theory::note::PitchClass;
 use std::{
-    collections::HashMap,
-    fs::{self, DirEntry},
+    fs,
     path::{Path, PathBuf},
 };
 use tui::widgets::ListState;
 
-lazy_static! {
-    static ref RE_SONG_TRANSPOSITION: Regex = Regex::new(r" \[([ABCDEFG][b#]?)\]").unwrap();
-}
-
-#[derive(PartialEq)]
+#[derive(PartialEq, Default)]
 pub enum AppState {
+    #[default]
     Default,
     Searching,
     Transposing,
-}
-
-impl Default for AppState {
-    fn default() -> AppState {
-        AppState::Default
-    }
+    Importing,
 }
 
 #[derive(Default)]
 pub struct App {
-    files: HashMap<FileType, String>,
+    pub library: Library,
     pub file_nav: FileNavigator,
     pub search_nav: FileNavigator,
     pub state: AppState,
     pub config: Config,
     pub song: Option<Song>,
+    pub capo: Option<i32>,
+    pub key_picker: ListState,
     pub input: String,
-    pub path: Vec<String>,
+    /// An imported song in ChordPro format and the file it will be saved to
+    pub import: Option<(PathBuf, String)>,
 }
 
-impl<'a> App {
+impl App {
     pub fn new(config: Config) -> Self {
-        let files = App::create_filemap(&config.path);
-        let mut all_files: Vec<FileType> = files.keys().cloned().collect();
+        let library = Library::scan(&config.path).with_selection(config.selection());
+        let mut all_files: Vec<FileType> = library.files().keys().cloned().collect();
         all_files.sort_by_key(FileType::name);
         App {
-            file_nav: FileNavigator::from_path(&config.path),
+            file_nav: FileNavigator(vec![Folder::from_path(&config.path, &library)]),
             search_nav: FileNavigator(vec![Folder {
                 name: String::from("Search"),
                 files: all_files,
                 state: ListState::default(),
             }]),
-            files,
+            library,
             config,
             ..Default::default()
         }
@@ -63,27 +57,111 @@ impl<'a> App {
         let file = self.get_nav().selected().cloned();
         if let Some(file) = file {
             match &file {
-                FileType::Folder(path) => self.get_nav_mut().open_path(&path),
+                FileType::Folder(path) => {
+                    let folder = Folder::from_path(path, &self.library);
+                    self.get_nav_mut().0.push(folder)
+                }
                 FileType::Playlist(_) => {
-                    let playlist = Playlist::from(self.files.get(&file).unwrap());
+                    let playlist = Playlist::from(self.library.files().get(&file).unwrap());
                     self.get_nav_mut().open_playlist(playlist)
                 }
-                FileType::Song(name) => match self.files.get(&file) {
-                    Some(song) => self.song = Some(Song::from(song.clone())),
-                    None => {
-                        if let Some(key) = RE_SONG_TRANSPOSITION.captures(&name) {
-                            let actual_name = RE_SONG_TRANSPOSITION.replace(&name, "");
-                            if let Some(song) =
-                                self.files.get(&FileType::Song(actual_name.to_string()))
-                            {
-                                self.song = Some(Song::in_key(
-                                    song.clone(),
-                                    PitchClass::from_str(key.get(1).unwrap().as_str()).unwrap(),
-                                ))
-                            }
-                        }
+                FileType::Song(name) => {
+                    if let Some(song) = self.library.find_song(name) {
+                        self.song = Some(song)
                     }
-                },
+                }
+            }
+        }
+    }
+
+    /// Returns the capo fret set for this session, or the one the song asks for
+    pub fn capo(&self) -> i32 {
+        match (self.capo, &self.song) {
+            (Some(capo), _) => capo,
+            (None, Some(song)) => song.capo,
+            (None, None) => 0,
+        }
+    }
+
+    pub fn change_capo(&mut self, amount: i32) {
+        self.capo = Some((self.capo() + amount).clamp(0, MAX_CAPO));
+    }
+
+    /// Puts the capo on the fret that gives the easiest shapes. In the key picker, the song is
+    /// moved to the selected key first.
+    pub fn suggest_capo(&mut self) {
+        let target = match self.state {
+            AppState::Transposing => self.picked_key(),
+            _ => None,
+        };
+        let suggestion = self
+            .song
+            .as_ref()
+            .and_then(|song| song.suggest_capo(target));
+        if let Some((capo, _)) = suggestion {
+            if target.is_some() {
+                self.pick_key();
+            }
+            self.capo = Some(capo)
+        }
+    }
+
+    /// Shows an imported song, so it can be checked before it is saved
+    pub fn preview_import(&mut self, path: PathBuf, chordpro: String) {
+        self.song = Some(Song::parse(
+            chordpro.clone(),
+            None,
+            &self.config.selection(),
+        ));
+        self.import = Some((path, chordpro));
+        self.state = AppState::Importing;
+    }
+
+    /// Saves the song that is being imported, returning the file it was saved to
+    pub fn save_import(&mut self) -> std::io::Result<Option<PathBuf>> {
+        match self.import.take() {
+            Some((path, chordpro)) => {
+                fs::write(&path, chordpro)?;
+                Ok(Some(path))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Opens the key picker with the current key of the song selected
+    pub fn open_key_picker(&mut self) {
+        self.state = AppState::Transposing;
+        self.key_picker.select(Some(
+            self.song
+                .as_ref()
+                .and_then(|song| song.key)
+                .map_or(0, |key| key.tonic.into_u8() as usize),
+        ));
+    }
+
+    pub fn key_picker_forward(&mut self, amount: usize) {
+        let selected = self.key_picker.selected().unwrap_or(0);
+        self.key_picker.select(Some((selected + amount) % 12));
+    }
+
+    pub fn key_picker_back(&mut self, amount: usize) {
+        let selected = self.key_picker.selected().unwrap_or(0);
+        self.key_picker
+            .select(Some((selected + 12 - amount % 12) % 12));
+    }
+
+    /// Returns the key selected in the key picker, in the mode of the song
+    fn picked_key(&self) -> Option<Key> {
+        let key = self.song.as_ref()?.key?;
+        let selected = self.key_picker.selected()?;
+        Some(Key::new(PitchClass::from_u8(selected as u8), key.mode))
+    }
+
+    /// Transposes the song to the key selected in the key picker
+    pub fn pick_key(&mut self) {
+        if let (Some(song), Some(selected)) = (&mut self.song, self.key_picker.selected()) {
+            if let Some(key) = song.key {
+                song.change_key(selected as i32 - key.tonic.into_u8() as i32);
             }
         }
     }
@@ -97,7 +175,8 @@ impl<'a> App {
     pub fn search(&mut self) {
         let input = &self.input.to_lowercase();
         let mut results: Vec<FileType> = self
-            .files
+            .library
+            .files()
             .iter()
             .filter_map(|(k, v)| {
                 if k.name().to_lowercase().contains(input) | v.to_lowercase().contains(input) {
@@ -115,63 +194,6 @@ impl<'a> App {
         }];
     }
 
-    fn create_filemap(path: &Path) -> HashMap<FileType, String> {
-        App::get_direntries(path)
-            .iter()
-            .filter_map(|file| {
-                let path = file.path();
-                if path.is_dir() {
-                    Some((FileType::Folder(path), String::new()))
-                } else {
-                    let extension = path.extension().unwrap_or_default().to_str().unwrap();
-                    if extension == "txt" {
-                        let filestring = fs::read_to_string(path).unwrap_or_default();
-                        Some((
-                            FileType::Song(
-                                Song::get_name(&filestring).unwrap_or_else(|| {
-                                    file.file_name().to_str().unwrap().to_string()
-                                }),
-                            ),
-                            filestring,
-                        ))
-                    } else if extension == "lst" {
-                        let filestring = fs::read_to_string(path).unwrap_or_default();
-                        Some((
-                            FileType::Playlist(
-                                Playlist::get_name(&filestring).unwrap_or_else(|| {
-                                    file.file_name().to_str().unwrap().to_string()
-                                }),
-                            ),
-                            filestring,
-                        ))
-                    } else {
-                        None
-                    }
-                }
-            })
-            .collect()
-    }
-
-    // Gets all DirEntry's that are not a folder
-    fn get_direntries(path: &Path) -> Vec<DirEntry> {
-        let dir = match fs::read_dir(path) {
-            Ok(d) => d,
-            Err(_) => return vec![],
-        };
-        dir.flat_map(|dir| {
-            let dir = dir.unwrap();
-            let path = dir.path();
-            if path.is_dir() {
-                let mut dirs = App::get_direntries(&path);
-                dirs.push(dir);
-                dirs
-            } else {
-                vec![dir]
-            }
-        })
-        .collect()
-    }
-
     pub fn get_nav(&self) -> &FileNavigator {
         match self.state {
             AppState::Searching => &self.search_nav,
@@ -187,43 +209,6 @@ impl<'a> App {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Hash)]
-pub enum FileType {
-    Folder(PathBuf),
-    Song(String),
-    Playlist(String),
-}
-
-impl FileType {
-    pub fn from_dir_entry(entry: DirEntry) -> Result<FileType, &'static str> {
-        let path = entry.path();
-        let name = path.file_name().unwrap().to_str().unwrap();
-        if path.is_dir() {
-            Ok(FileType::Folder(path))
-        } else if name.ends_with(".txt") {
-            Ok(FileType::Song(
-                Song::get_name(&fs::read_to_string(&path).unwrap())
-                    .unwrap_or_else(|| name.to_string()),
-            ))
-        } else if name.ends_with(".lst") {
-            Ok(FileType::Playlist(
-                Playlist::get_name(&fs::read_to_string(&path).unwrap())
-                    .unwrap_or_else(|| name.to_string()),
-            ))
-        } else {
-            Err("Unable to parse DirEntry to File")
-        }
-    }
-
-    pub fn name(&self) -> String {
-        match self {
-            FileType::Folder(path) => path.file_name().unwrap().to_str().unwrap().to_owned(),
-            FileType::Song(name) => name.to_owned(),
-            FileType::Playlist(name) => name.to_owned(),
-        }
-    }
-}
-
 #[derive(Default)]
 pub struct Folder {
     pub name: String,
@@ -232,21 +217,17 @@ pub struct Folder {
 }
 
 impl Folder {
-    fn from_path(path: &Path) -> Folder {
+    /// Lists a folder of the library, from the files found when the library was scanned
+    fn from_path(path: &Path, library: &Library) -> Folder {
         let name = path
             .file_name()
-            .unwrap_or_else(|| path.as_os_str())
+            .unwrap_or(path.as_os_str())
             .to_str()
             .unwrap()
             .to_string();
-        let mut files: Vec<FileType> = fs::read_dir(path)
-            .unwrap()
-            .filter_map(|dir| FileType::from_dir_entry(dir.unwrap()).ok())
-            .collect();
-        files.sort_by_key(FileType::name);
         Folder {
             name,
-            files,
+            files: library.folder(path),
             ..Default::default()
         }
     }
@@ -297,18 +278,10 @@ impl Folder {
 pub struct FileNavigator(Vec<Folder>);
 
 impl FileNavigator {
-    fn from_path(path: &Path) -> FileNavigator {
-        FileNavigator(vec![Folder::from_path(path)])
-    }
-
     fn open_playlist(&mut self, playlist: Playlist) {
         self.0.push(Folder::from_playlist(playlist));
     }
 
-    fn open_path(&mut self, path: &Path) {
-        self.0.push(Folder::from_path(path))
-    }
-
     pub fn path_back(&mut self) {
         if self.0.len() > 1 {
             self.0.pop();
@@ -335,3 +308,82 @@ impl FileNavigator {
         self.current().selected()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(song: &str) -> App {
+        App {
+            song: Some(Song::from(song.to_owned())),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn opens_the_key_picker_on_the_key_of_the_song() {
+        let mut app = app("{key: D}\n[D]One [G]two");
+        app.open_key_picker();
+        assert!(app.state == AppState::Transposing);
+        assert_eq!(app.key_picker.selected(), Some(2));
+    }
+
+    #[test]
+    fn wraps_around_the_key_picker() {
+        let mut app = app("{key: D}\n[D]One [G]two");
+        app.open_key_picker();
+        app.key_picker_back(3);
+        assert_eq!(app.key_picker.selected(), Some(11));
+        app.key_picker_forward(2);
+        assert_eq!(app.key_picker.selected(), Some(1));
+        app.key_picker_back(14);
+        assert_eq!(app.key_picker.selected(), Some(11));
+    }
+
+    #[test]
+    fn transposes_to_the_picked_key() {
+        let mut app = app("{key: D}\n[D]One [G]two");
+        app.open_key_picker();
+        app.key_picker_forward(5);
+        app.pick_key();
+        let song = app.song.unwrap();
+        assert_eq!(song.key.map(|key| key.to_string()), Some("G".into()));
+        assert_eq!(
+            song.original_key.map(|key| key.to_string()),
+            Some("D".into())
+        );
+        assert_eq!(song.chords(), ["G", "C"]);
+    }
+
+    #[test]
+    fn saves_imported_songs_after_the_preview() {
+        let path = std::env::temp_dir().join(format!("gpro-import-{}.txt", std::process::id()));
+        let mut app = App::default();
+        app.preview_import(path.clone(), String::from("{title: Grace}\n[G]Glory\n"));
+        assert!(app.state == AppState::Importing);
+        assert_eq!(
+            app.song.as_ref().map(|song| song.title.as_str()),
+            Some("Grace")
+        );
+        let saved = app.save_import();
+        let contents = fs::read_to_string(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(saved.unwrap(), Some(path));
+        assert_eq!(contents.unwrap(), "{title: Grace}\n[G]Glory\n");
+        assert_eq!(app.save_import().unwrap(), None);
+    }
+
+    #[test]
+    fn suggests_capos_for_the_picked_key() {
+        let mut app = app("{key: G}\n[G]One [C]two [D]three [Em]four");
+        app.suggest_capo();
+        assert_eq!(app.capo(), 0);
+        app.open_key_picker();
+        app.key_picker_forward(3);
+        app.suggest_capo();
+        assert_eq!(app.capo(), 3);
+        let song = app.song.unwrap();
+        assert_eq!(song.key.map(|key| key.to_string()), Some("Bb".into()));
+        assert_eq!(song.chords(), ["Bb", "Eb", "F", "Gm"]);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::parser::Selection;
 use serde::{
     de::{Deserializer, Visitor},
     ser::Serializer,
@@ -8,6 +9,7 @@ use termion::event::Key;
 use tui::style::{Color, Modifier, Style};
 
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub path: PathBuf,
     pub theme: Theme,
@@ -16,6 +18,14 @@ pub struct Config {
     pub auto_select_song: bool,
     pub extra_column_size: usize,
     pub column_padding: usize,
+    pub chord_display: ChordDisplay,
+    pub chord_notation: ChordNotation,
+    pub chorus_recall: ChorusRecall,
+    /// The instrument songs are shown for, which decides the content with selectors like
+    /// `{comment-guitar: ...}` that is shown
+    pub instrument: Option<String>,
+    /// The user songs are shown for, which selectors can also name
+    pub user: Option<String>,
 }
 
 impl Default for Config {
@@ -28,6 +38,11 @@ impl Default for Config {
             auto_select_song: false,
             extra_column_size: 15,
             column_padding: 2,
+            chord_display: ChordDisplay::Shapes,
+            chord_notation: ChordNotation::Letters,
+            chorus_recall: ChorusRecall::Full,
+            instrument: Some(String::from("guitar")),
+            user: None,
         }
     }
 }
@@ -38,12 +53,17 @@ impl Config {
         Ok(serde_yaml::from_str(&contents)?)
     }
 
+    /// The instrument and user songs are read for
+    pub fn selection(&self) -> Selection {
+        Selection {
+            instrument: self.instrument.clone(),
+            user: self.user.clone(),
+        }
+    }
+
     pub fn write_default(file: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
         if file.exists() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "File already exists",
-            )));
+            return Err(Box::new(std::io::Error::other("File already exists")));
         }
 
         let conf = Config::default();
@@ -53,11 +73,68 @@ impl Config {
     }
 }
 
+/// Which chords are shown when a capo is used
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ChordDisplay {
+    /// Chords as they sound
+    Concert,
+    /// Chord shapes to play relative to the capo
+    Shapes,
+    /// Sounding chords followed by the shapes to play
+    Both,
+}
+
+impl ChordDisplay {
+    pub fn next(self) -> Self {
+        match self {
+            ChordDisplay::Concert => ChordDisplay::Shapes,
+            ChordDisplay::Shapes => ChordDisplay::Both,
+            ChordDisplay::Both => ChordDisplay::Concert,
+        }
+    }
+}
+
+/// How chords are written
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ChordNotation {
+    /// Letter names (C, F, G, Am)
+    Letters,
+    /// Nashville numbers relative to the key (1, 4, 5, 6m)
+    Nashville,
+    /// Roman numerals relative to the key (I, IV, V, vi)
+    Roman,
+}
+
+impl ChordNotation {
+    pub fn next(self) -> Self {
+        match self {
+            ChordNotation::Letters => ChordNotation::Nashville,
+            ChordNotation::Nashville => ChordNotation::Roman,
+            ChordNotation::Roman => ChordNotation::Letters,
+        }
+    }
+}
+
+/// How a chorus repeated with `{chorus}` is shown
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ChorusRecall {
+    /// The whole chorus again
+    Full,
+    /// Only a "Chorus" label
+    Label,
+    /// The label followed by the first line of the chorus
+    FirstLine,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Theme {
     pub title: ConfStyle,
     pub comment: ConfStyle,
+    pub comment_italic: ConfStyle,
+    pub comment_box: ConfStyle,
     pub chord: ConfStyle,
+    pub annotation: ConfStyle,
     pub lyrics: ConfStyle,
     pub selected: ConfStyle,
     pub folder: ConfStyle,
@@ -74,7 +151,16 @@ impl Default for Theme {
             comment: ConfStyle::default()
                 .fg(Color::Red)
                 .add_modifier(Modifier::BOLD),
+            comment_italic: ConfStyle::default()
+                .fg(Color::Red)
+                .add_modifier(Modifier::ITALIC),
+            comment_box: ConfStyle::default()
+                .fg(Color::Red)
+                .add_modifier(Modifier::BOLD),
             chord: ConfStyle::default().fg(Color::Blue),
+            annotation: ConfStyle::default()
+                .fg(Color::Magenta)
+                .add_modifier(Modifier::ITALIC),
             lyrics: ConfStyle::default(),
             selected: ConfStyle::default()
                 .fg(Color::Green)
@@ -87,6 +173,7 @@ impl Default for Theme {
 }
 
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Keybinds {
     pub up: SerDeKey,
     pub down: SerDeKey,
@@ -98,6 +185,11 @@ pub struct Keybinds {
     pub col_size_dec: SerDeKey,
     pub search: SerDeKey,
     pub transpose: SerDeKey,
+    pub capo_inc: SerDeKey,
+    pub capo_dec: SerDeKey,
+    pub capo_suggest: SerDeKey,
+    pub chord_display: SerDeKey,
+    pub chord_notation: SerDeKey,
     pub quit: SerDeKey,
 }
 
@@ -114,6 +206,11 @@ impl Default for Keybinds {
             col_size_dec: SerDeKey(Key::Home),
             search: SerDeKey(Key::Char('/')),
             transpose: SerDeKey(Key::Char('t')),
+            capo_inc: SerDeKey(Key::Char('+')),
+            capo_dec: SerDeKey(Key::Char('-')),
+            capo_suggest: SerDeKey(Key::Char('=')),
+            chord_display: SerDeKey(Key::Char('c')),
+            chord_notation: SerDeKey(Key::Char('n')),
             quit: SerDeKey(Key::Ctrl('c')),
         }
     }
@@ -137,7 +234,7 @@ impl Default for Icons {
 }
 
 /// Style replacement which uses SerDeModifier in order to be readable when serialized
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct ConfStyle {
     pub fg: Option<Color>,
     pub bg: Option<Color>,
@@ -174,16 +271,6 @@ impl ConfStyle {
     }
 }
 
-impl Default for ConfStyle {
-    fn default() -> Self {
-        ConfStyle {
-            fg: None,
-            bg: None,
-            modifiers: vec![],
-        }
-    }
-}
-
 /// Termion key wrapper that has serialize and deserialize
 #[derive(Clone)]
 pub struct SerDeKey(Key);
@@ -313,7 +400,7 @@ impl Serialize for SerDeModifier {
             Modifier::CROSSED_OUT => "Strikethrough",
             _ => unreachable!(),
         };
-        serializer.serialize_str(&string)
+        serializer.serialize_str(string)
     }
 }
 
@@ -355,3 +442,83 @@ impl<'de> Visitor<'de> for ModifierVisitor {
         Ok(SerDeModifier(modifier))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A config written before capo mode, with a changed path, chord color and quit key
+    const OLD_CONFIG: &str = "---
+path: /music
+theme:
+  title:
+    fg: Blue
+    bg: ~
+    modifiers:
+      - Bold
+  comment:
+    fg: Red
+    bg: ~
+    modifiers:
+      - Bold
+  chord:
+    fg: Green
+    bg: ~
+    modifiers: []
+  lyrics:
+    fg: ~
+    bg: ~
+    modifiers: []
+  selected:
+    fg: Green
+    bg: ~
+    modifiers:
+      - Bold
+  folder:
+    fg: Yellow
+    bg: ~
+    modifiers: []
+  song:
+    fg: ~
+    bg: ~
+    modifiers: []
+  playlist:
+    fg: Cyan
+    bg: ~
+    modifiers: []
+keybinds:
+  up: Up
+  down: Down
+  next: Right
+  back: Left
+  jump_up: PageUp
+  jump_down: PageDown
+  col_size_inc: End
+  col_size_dec: Home
+  search: /
+  transpose: t
+  quit: q
+icons:
+  folder: \"+ \"
+  playlist: \"= \"
+  song: \"- \"
+auto_select_song: false
+extra_column_size: 15
+column_padding: 2
+";
+
+    #[test]
+    fn loads_configs_without_newer_settings() {
+        let config: Config = serde_yaml::from_str(OLD_CONFIG).unwrap();
+        assert_eq!(config.path.to_str(), Some("/music"));
+        assert_eq!(config.theme.chord.to_style().fg, Some(Color::Green));
+        assert_eq!(config.keybinds.quit.to_key(), Key::Char('q'));
+        // Settings the file doesn't have are left at their defaults
+        assert!(config.chord_display == ChordDisplay::Shapes);
+        assert_eq!(config.keybinds.capo_inc.to_key(), Key::Char('+'));
+        assert_eq!(
+            config.theme.annotation.to_style().fg,
+            Config::default().theme.annotation.to_style().fg
+        );
+    }
+}
@@ -0,0 +1,135 @@
+extern crate rust_music_theory as rustmt;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use rustmt::note::PitchClass;
+
+lazy_static! {
+    static ref RE_CHORD: Regex = Regex::new(r"^([ABCDEFG][b#]?)([^/]*)(?:/.*)?$").unwrap();
+}
+
+/// Frets to hold down on each string from low to high, where `None` means the string is muted
+/// and `Some(0)` means it is played open
+pub type Fingering = [Option<u8>; 6];
+
+const X: Option<u8> = None;
+const fn f(fret: u8) -> Option<u8> {
+    Some(fret)
+}
+
+/// Chords commonly played in first position
+const OPEN_FINGERINGS: &[(&str, Fingering)] = &[
+    ("C", [X, f(3), f(2), f(0), f(1), f(0)]),
+    ("C7", [X, f(3), f(2), f(3), f(1), f(0)]),
+    ("Cmaj7", [X, f(3), f(2), f(0), f(0), f(0)]),
+    ("D", [X, X, f(0), f(2), f(3), f(2)]),
+    ("Dm", [X, X, f(0), f(2), f(3), f(1)]),
+    ("D7", [X, X, f(0), f(2), f(1), f(2)]),
+    ("Dm7", [X, X, f(0), f(2), f(1), f(1)]),
+    ("Dmaj7", [X, X, f(0), f(2), f(2), f(2)]),
+    ("Dsus2", [X, X, f(0), f(2), f(3), f(0)]),
+    ("Dsus4", [X, X, f(0), f(2), f(3), f(3)]),
+    ("E", [f(0), f(2), f(2), f(1), f(0), f(0)]),
+    ("Em", [f(0), f(2), f(2), f(0), f(0), f(0)]),
+    ("E7", [f(0), f(2), f(0), f(1), f(0), f(0)]),
+    ("Em7", [f(0), f(2), f(0), f(0), f(0), f(0)]),
+    ("Esus4", [f(0), f(2), f(2), f(2), f(0), f(0)]),
+    ("Fmaj7", [X, X, f(3), f(2), f(1), f(0)]),
+    ("G", [f(3), f(2), f(0), f(0), f(0), f(3)]),
+    ("G7", [f(3), f(2), f(0), f(0), f(0), f(1)]),
+    ("A", [X, f(0), f(2), f(2), f(2), f(0)]),
+    ("Am", [X, f(0), f(2), f(2), f(1), f(0)]),
+    ("A7", [X, f(0), f(2), f(0), f(2), f(0)]),
+    ("Am7", [X, f(0), f(2), f(0), f(1), f(0)]),
+    ("Amaj7", [X, f(0), f(2), f(1), f(2), f(0)]),
+    ("Asus2", [X, f(0), f(2), f(2), f(0), f(0)]),
+    ("Asus4", [X, f(0), f(2), f(2), f(3), f(0)]),
+    ("B7", [X, f(2), f(1), f(2), f(0), f(2)]),
+];
+
+/// Barre chord shapes with the root on the low E string, relative to the barre
+const E_SHAPES: &[(&str, Fingering)] = &[
+    ("", [f(0), f(2), f(2), f(1), f(0), f(0)]),
+    ("m", [f(0), f(2), f(2), f(0), f(0), f(0)]),
+    ("7", [f(0), f(2), f(0), f(1), f(0), f(0)]),
+    ("m7", [f(0), f(2), f(0), f(0), f(0), f(0)]),
+    ("maj7", [f(0), X, f(1), f(1), f(0), X]),
+    ("sus4", [f(0), f(2), f(2), f(2), f(0), f(0)]),
+];
+
+/// Barre chord shapes with the root on the A string, relative to the barre
+const A_SHAPES: &[(&str, Fingering)] = &[
+    ("", [X, f(0), f(2), f(2), f(2), f(0)]),
+    ("m", [X, f(0), f(2), f(2), f(1), f(0)]),
+    ("7", [X, f(0), f(2), f(0), f(2), f(0)]),
+    ("m7", [X, f(0), f(2), f(0), f(1), f(0)]),
+    ("maj7", [X, f(0), f(2), f(1), f(2), f(0)]),
+    ("sus2", [X, f(0), f(2), f(2), f(0), f(0)]),
+    ("sus4", [X, f(0), f(2), f(2), f(3), f(0)]),
+];
+
+/// Finds a guitar fingering for a chord, preferring open chords and otherwise the barre chord
+/// closest to the nut. Returns `None` for chords that aren't in the chord library.
+pub fn guitar(chord: &str) -> Option<Fingering> {
+    // Bass notes of slash chords are left to the player
+    let caps = RE_CHORD.captures(chord)?;
+    let root_name = caps.get(1).unwrap().as_str();
+    let quality = caps.get(2).unwrap().as_str();
+    let name = format!("{}{}", root_name, quality);
+    if let Some((_, fingering)) = OPEN_FINGERINGS.iter().find(|(open, _)| *open == name) {
+        return Some(*fingering);
+    }
+    let root = PitchClass::from_str(root_name)?.into_u8();
+
+    // Barres on the nut would be open chords, so they move up to the twelfth fret instead
+    let barre = |string_root: u8| match (root + 12 - string_root) % 12 {
+        0 => 12,
+        fret => fret,
+    };
+    let shape = |shapes: &[(&str, Fingering)], fret: u8| {
+        shapes
+            .iter()
+            .find(|(name, _)| *name == quality)
+            .map(|(_, shape)| (fret, shift(*shape, fret)))
+    };
+    let e_shape = shape(E_SHAPES, barre(PitchClass::E.into_u8()));
+    let a_shape = shape(A_SHAPES, barre(PitchClass::A.into_u8()));
+    match (e_shape, a_shape) {
+        (Some(e), Some(a)) if a.0 < e.0 => Some(a.1),
+        (Some(e), _) => Some(e.1),
+        (None, a) => a.map(|a| a.1),
+    }
+}
+
+fn shift(shape: Fingering, frets: u8) -> Fingering {
+    let mut shifted = shape;
+    shifted
+        .iter_mut()
+        .for_each(|fret| *fret = fret.map(|fret| fret + frets));
+    shifted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_open_chords() {
+        assert_eq!(guitar("C"), Some([X, f(3), f(2), f(0), f(1), f(0)]));
+        assert_eq!(guitar("Am/G"), guitar("Am"));
+    }
+
+    #[test]
+    fn moves_barre_shapes_up_the_neck() {
+        // F and Bm are played as barre chords on the first and second fret
+        assert_eq!(guitar("F"), Some([f(1), f(3), f(3), f(2), f(1), f(1)]));
+        assert_eq!(guitar("Bm"), Some([X, f(2), f(4), f(4), f(3), f(2)]));
+        assert_eq!(guitar("Ebm7"), Some([X, f(6), f(8), f(6), f(7), f(6)]));
+    }
+
+    #[test]
+    fn leaves_out_unknown_chords() {
+        assert_eq!(guitar("C13b9"), None);
+        assert_eq!(guitar("N.C."), None);
+    }
+}
@@ -0,0 +1,260 @@
+mod html;
+mod latex;
+mod openlyrics;
+mod opensong;
+mod pdf;
+mod text;
+
+use crate::{
+    conf::Config,
+    parser::{Playlist, Recalled, Selection, Song, SongLine, SongString},
+};
+use std::{fs, io, path::Path, str::FromStr};
+use tui::style::Color;
+
+/// The formats songs can be exported to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Pdf,
+    Html,
+    Text,
+    Markdown,
+    Latex,
+    OpenLyrics,
+    OpenSong,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "pdf" => Ok(Format::Pdf),
+            "html" => Ok(Format::Html),
+            "txt" | "text" => Ok(Format::Text),
+            "md" | "markdown" => Ok(Format::Markdown),
+            "tex" | "latex" => Ok(Format::Latex),
+            "openlyrics" => Ok(Format::OpenLyrics),
+            "opensong" => Ok(Format::OpenSong),
+            _ => Err(format!("Unknown export format '{}'", format)),
+        }
+    }
+}
+
+impl Format {
+    /// Whether files in this format hold a single song, so that every song needs its own file
+    pub fn single_song(&self) -> bool {
+        matches!(self, Format::OpenLyrics | Format::OpenSong)
+    }
+
+    /// The file extension of files in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Pdf => "pdf",
+            Format::Html => "html",
+            Format::Text => "txt",
+            Format::Markdown => "md",
+            Format::Latex => "tex",
+            Format::OpenLyrics | Format::OpenSong => "xml",
+        }
+    }
+}
+
+/// Exports songs in the given format. When a title is given, the songs are exported as a book
+/// with that title, like a playlist. Text formats wrap lines to the given width.
+pub fn export(
+    songs: &[Song],
+    title: Option<&str>,
+    format: Format,
+    width: usize,
+    config: &Config,
+) -> Vec<u8> {
+    match format {
+        Format::Pdf => pdf::export(songs, title, config),
+        Format::Html => html::export(songs, title, config),
+        Format::Text => text::plain(songs, title, width, config),
+        Format::Markdown => text::markdown(songs, title, width, config),
+        Format::Latex => latex::export(songs, title, config),
+        Format::OpenLyrics => songs
+            .first()
+            .map(|song| openlyrics::export(song, config))
+            .unwrap_or_default(),
+        Format::OpenSong => songs
+            .first()
+            .map(|song| opensong::export(song, config))
+            .unwrap_or_default(),
+    }
+}
+
+/// Exports a whole library as a static website into the given directory
+pub fn export_site(
+    songs: &[(String, Song)],
+    playlists: &[(Playlist, Vec<Song>)],
+    dir: &Path,
+    config: &Config,
+) -> io::Result<()> {
+    html::site(songs, playlists, dir, config)
+}
+
+/// Exports every song into its own file in the given directory, named after its title
+pub fn export_each(
+    songs: &[Song],
+    dir: &Path,
+    format: Format,
+    width: usize,
+    config: &Config,
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let mut slugs = vec![];
+    for song in songs {
+        let slug = unique_slug(&song.title, &mut slugs);
+        let contents = export(std::slice::from_ref(song), None, format, width, config);
+        fs::write(
+            dir.join(format!("{}.{}", slug, format.extension())),
+            contents,
+        )?;
+    }
+    Ok(())
+}
+
+/// Turns a name into a file name that is unique among the given slugs
+fn unique_slug(name: &str, slugs: &mut Vec<String>) -> String {
+    let base: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| match c.is_alphanumeric() {
+            true => c,
+            false => '-',
+        })
+        .collect::<String>()
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-");
+    let mut slug = base.clone();
+    let mut i = 1;
+    while slugs.contains(&slug) {
+        i += 1;
+        slug = format!("{}-{}", base, i);
+    }
+    slugs.push(slug.clone());
+    slug
+}
+
+/// Describes the key and capo of a song, like "Key: A" and "Capo 2 (G shapes)"
+fn details(song: &Song) -> Vec<String> {
+    let mut details = vec![];
+    if let Some(key) = song.key {
+        details.push(format!("Key: {}", key));
+    }
+    if song.capo > 0 {
+        match song.key {
+            Some(key) => details.push(format!(
+                "Capo {} ({} shapes)",
+                song.capo,
+                key.transpose(-song.capo)
+            )),
+            None => details.push(format!("Capo {}", song.capo)),
+        }
+    }
+    details
+}
+
+/// Splits song content into sections at empty lines and where choruses start or end
+fn sections(content: &[SongLine]) -> Vec<Vec<&SongLine>> {
+    let mut sections: Vec<Vec<&SongLine>> = vec![];
+    let mut section: Vec<&SongLine> = vec![];
+    for line in content {
+        if line.blocks.is_empty() {
+            sections.push(section);
+            section = vec![];
+            continue;
+        }
+        if section
+            .last()
+            .is_some_and(|last| last.chorus != line.chorus)
+        {
+            sections.push(section);
+            section = vec![];
+        }
+        section.push(line);
+    }
+    sections.push(section);
+    sections.retain(|section| !section.is_empty());
+    sections
+}
+
+/// Leaves out the lines of other instruments and users, and the labels of sections repeated
+/// with `{chorus}`, for formats that repeat the section itself in their order of sections
+fn exported_lines(content: &[SongLine], selection: &Selection) -> Vec<SongLine> {
+    content
+        .iter()
+        .filter(|line| line.is_selected(selection) && line.recalled != Some(Recalled::Label))
+        .cloned()
+        .collect()
+}
+
+/// Whether a section repeats an earlier section with `{chorus}`
+fn is_recalled(section: &[&SongLine]) -> bool {
+    section.iter().all(|line| line.recalled.is_some())
+}
+
+/// The text of a section, which tells a repeated section which section it repeats
+fn section_text(section: &[&SongLine]) -> String {
+    section
+        .iter()
+        .map(|line| line_text(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Joins the chords, lyrics and comments of a line into a single string
+fn line_text(line: &SongLine) -> String {
+    line.blocks
+        .iter()
+        .flat_map(|block| block.0.iter())
+        .map(SongString::text)
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
+
+/// Whether a line only holds comments, like section labels
+fn is_comment(line: &SongLine) -> bool {
+    line.blocks
+        .iter()
+        .flat_map(|block| block.0.iter())
+        .all(SongString::is_comment)
+}
+
+/// Escapes text for use in HTML and XML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Converts terminal colors into colors that are readable on paper
+fn rgb(color: Option<Color>) -> (f32, f32, f32) {
+    match color {
+        None | Some(Color::Reset) | Some(Color::Black) | Some(Color::White) => (0.0, 0.0, 0.0),
+        Some(Color::Red) => (0.75, 0.0, 0.0),
+        Some(Color::Green) => (0.0, 0.5, 0.0),
+        Some(Color::Yellow) => (0.6, 0.5, 0.0),
+        Some(Color::Blue) => (0.0, 0.0, 0.75),
+        Some(Color::Magenta) => (0.6, 0.0, 0.6),
+        Some(Color::Cyan) => (0.0, 0.5, 0.6),
+        Some(Color::Gray) => (0.5, 0.5, 0.5),
+        Some(Color::DarkGray) => (0.35, 0.35, 0.35),
+        Some(Color::LightRed) => (0.9, 0.3, 0.3),
+        Some(Color::LightGreen) => (0.3, 0.7, 0.3),
+        Some(Color::LightYellow) => (0.75, 0.65, 0.1),
+        Some(Color::LightBlue) => (0.3, 0.4, 0.9),
+        Some(Color::LightMagenta) => (0.8, 0.3, 0.8),
+        Some(Color::LightCyan) => (0.2, 0.65, 0.75),
+        Some(Color::Rgb(r, g, b)) => (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0),
+        Some(Color::Indexed(_)) => (0.0, 0.0, 0.0),
+    }
+}
@@ -0,0 +1,428 @@
+use super::{escape, rgb, unique_slug};
+use crate::{
+    conf::{ChordDisplay, ConfStyle, Config},
+    parser::{Playlist, Song, SongString},
+};
+use std::{fs, io, path::Path};
+use tui::style::Modifier;
+
+/// Transposes the chords of a song in the browser, spelling them like `Key::spell` does. Chords
+/// are kept as they sound, and shown as shapes for the capo like `Song::with_capo` does.
+const SCRIPT: &str = r##"
+const SHARPS = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+const FLATS = ["C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B"];
+const FLAT_KEYS = [0, 1, 3, 5, 8, 10];
+const NOTES = { C: 0, D: 2, E: 4, F: 5, G: 7, A: 9, B: 11 };
+
+function pitch(note) {
+  const accidental = { "#": 1, "b": -1 }[note[1]] || 0;
+  return (NOTES[note[0]] + accidental + 12) % 12;
+}
+
+function spelling(relativeMajor) {
+  return FLAT_KEYS.includes((relativeMajor + 12) % 12) ? FLATS : SHARPS;
+}
+
+function move(chord, semitones, names) {
+  return chord.replace(/[A-G][b#]?/g, (note) => names[(pitch(note) + semitones + 12) % 12]);
+}
+
+function transpose(song, semitones) {
+  const shift = (Number(song.dataset.transposition) + semitones + 12) % 12;
+  song.dataset.transposition = shift;
+  const capo = Number(song.dataset.capo);
+  let names = SHARPS;
+  let shapeNames = SHARPS;
+  const key = song.querySelector(".key");
+  if (key) {
+    const tonic = (pitch(key.dataset.tonic) + shift) % 12;
+    const relativeMajor = (tonic + Number(key.dataset.relative)) % 12;
+    names = spelling(relativeMajor);
+    shapeNames = spelling(relativeMajor - capo);
+    key.textContent = names[tonic] + key.dataset.mode;
+    const shapeKey = song.querySelector(".shape-key");
+    if (shapeKey) {
+      shapeKey.textContent = shapeNames[(tonic - capo + 12) % 12] + key.dataset.mode;
+    }
+  }
+  song.querySelectorAll(".chord[data-chord]").forEach((chord) => {
+    const sounding = move(chord.dataset.chord, shift, names);
+    const shape = move(chord.dataset.chord, shift - capo, shapeNames);
+    chord.textContent = {
+      concert: sounding,
+      shapes: shape,
+      both: sounding + "(" + shape + ")",
+    }[song.dataset.display];
+  });
+}
+
+document.querySelectorAll("button[data-transpose]").forEach((button) => {
+  button.addEventListener("click", () =>
+    transpose(button.closest(".song"), Number(button.dataset.transpose))
+  );
+});
+"##;
+
+/// Exports songs as a single self-contained page. When a title is given, the page starts with
+/// a table of contents.
+pub fn export(songs: &[Song], title: Option<&str>, config: &Config) -> Vec<u8> {
+    let page_title = title
+        .or_else(|| songs.first().map(|song| song.title.as_str()))
+        .unwrap_or("Songbook");
+    let mut body = String::new();
+    if title.is_some() || songs.len() > 1 {
+        body += &format!(
+            "<h1 class=\"book\">{}</h1>\n<nav><ol>\n",
+            escape(page_title)
+        );
+        for (i, song) in songs.iter().enumerate() {
+            body += &format!(
+                "<li><a href=\"#song-{}\">{}</a></li>\n",
+                i + 1,
+                escape(&song.title)
+            );
+        }
+        body += "</ol></nav>\n";
+    }
+    for (i, song) in songs.iter().enumerate() {
+        body += &self::song(song, &format!("song-{}", i + 1), config);
+    }
+    page(page_title, &body, None, config).into_bytes()
+}
+
+/// Exports a library as a static site, with a page for every song and playlist and an index
+/// linking to all of them
+pub fn site(
+    songs: &[(String, Song)],
+    playlists: &[(Playlist, Vec<Song>)],
+    dir: &Path,
+    config: &Config,
+) -> io::Result<()> {
+    fs::create_dir_all(dir.join("songs"))?;
+    fs::create_dir_all(dir.join("playlists"))?;
+    let mut slugs: Vec<String> = vec![];
+    let mut index = String::from("<h1 class=\"book\">Songbook</h1>\n");
+
+    if !playlists.is_empty() {
+        index += "<h2>Playlists</h2>\n<ul>\n";
+        for (playlist, playlist_songs) in playlists {
+            let slug = unique_slug(&playlist.title, &mut slugs);
+            let mut body = format!("<h1 class=\"book\">{}</h1>\n", escape(&playlist.title));
+            for (i, song) in playlist_songs.iter().enumerate() {
+                body += &self::song(song, &format!("song-{}", i + 1), config);
+            }
+            let contents = page(&playlist.title, &body, Some("../index.html"), config);
+            fs::write(
+                dir.join("playlists").join(format!("{}.html", slug)),
+                contents,
+            )?;
+            index += &format!(
+                "<li><a href=\"playlists/{}.html\">{}</a></li>\n",
+                slug,
+                escape(&playlist.title)
+            );
+        }
+        index += "</ul>\n";
+    }
+
+    index += "<h2>Songs</h2>\n<ul>\n";
+    for (name, song) in songs {
+        let slug = unique_slug(name, &mut slugs);
+        let body = self::song(song, "song-1", config);
+        let contents = page(name, &body, Some("../index.html"), config);
+        fs::write(dir.join("songs").join(format!("{}.html", slug)), contents)?;
+        index += &format!(
+            "<li><a href=\"songs/{}.html\">{}</a></li>\n",
+            slug,
+            escape(name)
+        );
+    }
+    index += "</ul>\n";
+
+    fs::write(
+        dir.join("index.html"),
+        page("Songbook", &index, None, config),
+    )
+}
+
+fn page(title: &str, body: &str, back: Option<&str>, config: &Config) -> String {
+    let back = match back {
+        Some(href) => format!("<a class=\"back\" href=\"{}\">Index</a>\n", href),
+        None => String::new(),
+    };
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n{}{}<script>{}</script>\n\
+         </body>\n</html>\n",
+        escape(title),
+        stylesheet(config),
+        back,
+        body,
+        SCRIPT
+    )
+}
+
+fn stylesheet(config: &Config) -> String {
+    format!(
+        "body {{ font-family: sans-serif; max-width: 60em; margin: 0 auto; padding: 1em; }}\n\
+         .song {{ margin-bottom: 3em; }}\n\
+         .song h1 {{ margin-bottom: 0; {} }}\n\
+         .song h2 {{ margin-top: 0; font-weight: normal; }}\n\
+         .details button {{ margin-left: 0.5em; }}\n\
+         .line {{ display: flex; flex-wrap: wrap; align-items: flex-end; min-height: 1.2em; }}\n\
+         .chorus {{ border-left: 3px solid; padding-left: 0.8em; {} }}\n\
+         .segment {{ display: inline-flex; flex-direction: column; }}\n\
+         .chord {{ min-height: 1.2em; padding-right: 0.4em; white-space: pre; {} }}\n\
+         .no-chords .chord {{ display: none; }}\n\
+         .lyrics {{ white-space: pre-wrap; {} }}\n\
+         .comment {{ {} }}\n\
+         .comment-italic {{ {} }}\n\
+         .comment-box {{ border: 1px solid; padding: 0 0.3em; {} }}\n\
+         .highlight {{ background: yellow; }}\n\
+         .annotation {{ {} }}\n",
+        css(&config.theme.title),
+        border_color(&config.theme.comment),
+        css(&config.theme.chord),
+        css(&config.theme.lyrics),
+        css(&config.theme.comment),
+        css(&config.theme.comment_italic),
+        css(&config.theme.comment_box),
+        css(&config.theme.annotation),
+    )
+}
+
+/// Converts a theme style into CSS declarations
+fn css(style: &ConfStyle) -> String {
+    let style = style.to_style();
+    let mut css = String::new();
+    if style.fg.is_some() {
+        let (r, g, b) = rgb(style.fg);
+        css += &format!(
+            "color: rgb({}, {}, {}); ",
+            (r * 255.0) as u8,
+            (g * 255.0) as u8,
+            (b * 255.0) as u8
+        );
+    }
+    if style.add_modifier.contains(Modifier::BOLD) {
+        css += "font-weight: bold; ";
+    }
+    if style.add_modifier.contains(Modifier::ITALIC) {
+        css += "font-style: italic; ";
+    }
+    if style.add_modifier.contains(Modifier::UNDERLINED) {
+        css += "text-decoration: underline; ";
+    }
+    css
+}
+
+fn border_color(style: &ConfStyle) -> String {
+    let (r, g, b) = rgb(style.fg);
+    format!(
+        "border-color: rgb({}, {}, {});",
+        (r * 255.0) as u8,
+        (g * 255.0) as u8,
+        (b * 255.0) as u8
+    )
+}
+
+fn song(song: &Song, id: &str, config: &Config) -> String {
+    // Chords are kept as they sound, so the page can move them and the capo shapes together
+    let display = match (song.capo, config.chord_display) {
+        (0, _) | (_, ChordDisplay::Concert) => "concert",
+        (_, ChordDisplay::Shapes) => "shapes",
+        (_, ChordDisplay::Both) => "both",
+    };
+    let mut html = format!(
+        "<article class=\"song\" id=\"{}\" data-transposition=\"0\" data-capo=\"{}\" \
+         data-display=\"{}\">\n<h1>{}</h1>\n",
+        id,
+        song.capo,
+        display,
+        escape(&song.title)
+    );
+    if !song.subtitle.is_empty() {
+        html += &format!("<h2>{}</h2>\n", escape(&song.subtitle));
+    }
+
+    html += "<p class=\"details\">";
+    if let Some(key) = song.key {
+        let tonic = key.spell(key.tonic);
+        let relative =
+            (key.relative_major().into_u8() as i32 - key.tonic.into_u8() as i32).rem_euclid(12);
+        html += &format!(
+            "Key: <span class=\"key\" data-tonic=\"{}\" data-mode=\"{}\" data-relative=\"{}\">{}</span> ",
+            tonic,
+            escape(&key.to_string()[tonic.len()..]),
+            relative,
+            key
+        );
+    }
+    if song.capo > 0 {
+        html += &format!("Capo {} ", song.capo);
+        if let Some(key) = song.key.filter(|_| display != "concert") {
+            html += &format!(
+                "(shapes in <span class=\"shape-key\">{}</span>) ",
+                key.transpose(-song.capo)
+            );
+        }
+    }
+    html += "<button data-transpose=\"-1\">&minus;</button>\
+             <button data-transpose=\"1\">+</button></p>\n";
+
+    let sounding = song.display(0, config);
+    for (line, sounding) in song.display(song.capo, config).iter().zip(sounding.iter()) {
+        let mut class = String::from("line");
+        if line.chorus {
+            class += " chorus";
+        }
+
+        // Every chord and annotation starts a segment that stacks it on top of the lyrics it
+        // belongs to
+        let mut segments: Vec<(Option<String>, String)> = vec![];
+        let sounding = sounding.blocks.iter().flat_map(|block| block.0.iter());
+        for (string, sounding) in line
+            .blocks
+            .iter()
+            .flat_map(|block| block.0.iter())
+            .zip(sounding)
+        {
+            let text = match string {
+                SongString::Chord(chord) => {
+                    let chord = format!(
+                        "<span class=\"chord\" data-chord=\"{}\">{}</span>",
+                        escape(sounding.text()),
+                        escape(chord)
+                    );
+                    segments.push((Some(chord), String::new()));
+                    continue;
+                }
+                SongString::Annotation(annotation) => {
+                    let annotation = format!(
+                        "<span class=\"chord annotation\">{}</span>",
+                        escape(annotation)
+                    );
+                    segments.push((Some(annotation), String::new()));
+                    continue;
+                }
+                SongString::Text(text) => escape(text),
+                comment => {
+                    let class = match comment {
+                        SongString::CommentItalic(_) => "comment-italic",
+                        SongString::CommentBox(_) => "comment-box",
+                        SongString::Highlight(_) => "comment highlight",
+                        _ => "comment",
+                    };
+                    format!(
+                        "<span class=\"{}\">{}</span>",
+                        class,
+                        escape(comment.text())
+                    )
+                }
+            };
+            match segments.last_mut() {
+                Some((_, lyrics)) => *lyrics += &text,
+                None => segments.push((None, text)),
+            }
+        }
+        if segments.iter().all(|(chord, _)| chord.is_none()) {
+            class += " no-chords";
+        }
+
+        html += &format!("<div class=\"{}\">", class);
+        for (chord, lyrics) in segments {
+            let chord = chord.unwrap_or_else(|| String::from("<span class=\"chord\"></span>"));
+            html += &format!(
+                "<span class=\"segment\">{}<span class=\"lyrics\">{}</span></span>",
+                chord, lyrics
+            );
+        }
+        html += "</div>\n";
+    }
+    html + "</article>\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(chordpro: &str) -> Song {
+        Song::from(chordpro.to_owned())
+    }
+
+    fn export(songs: &[Song], title: Option<&str>) -> String {
+        String::from_utf8(super::export(songs, title, &Config::default())).unwrap()
+    }
+
+    #[test]
+    fn exports_chords_over_lyrics() {
+        let html = export(
+            &[song("{title: Grace}\n{key: G}\nA[G]mazing [C]grace")],
+            None,
+        );
+        assert!(html.contains("<title>Grace</title>"));
+        assert!(html.contains(
+            "<span class=\"key\" data-tonic=\"G\" data-mode=\"\" data-relative=\"0\">G</span>"
+        ));
+        assert!(html.contains(
+            "<span class=\"segment\"><span class=\"chord\" data-chord=\"C\">C</span>\
+             <span class=\"lyrics\">grace</span></span>"
+        ));
+        assert!(html.contains("<button data-transpose=\"1\">"));
+    }
+
+    #[test]
+    fn exports_playlists_with_contents() {
+        let songs = [song("{title: One}"), song("{title: Two & three}")];
+        let html = export(&songs, Some("Sunday"));
+        assert!(html.contains("<h1 class=\"book\">Sunday</h1>"));
+        assert!(html.contains("<li><a href=\"#song-2\">Two &amp; three</a></li>"));
+        assert!(html.contains("<article class=\"song\" id=\"song-2\""));
+    }
+
+    #[test]
+    fn makes_unique_slugs() {
+        let mut slugs = vec![];
+        assert_eq!(unique_slug("Amazing Grace!", &mut slugs), "amazing-grace");
+        assert_eq!(unique_slug("Amazing grace", &mut slugs), "amazing-grace-2");
+    }
+
+    #[test]
+    fn exports_sites() {
+        let dir = std::env::temp_dir().join(format!("gpro-site-{}", std::process::id()));
+        let songs = vec![(String::from("Grace"), song("{title: Grace}\n[G]Glory"))];
+        let playlists = vec![(Playlist::from("Sunday\nGrace"), vec![songs[0].1.clone()])];
+        let result = site(&songs, &playlists, &dir, &Config::default());
+        let index = fs::read_to_string(dir.join("index.html"));
+        let page = fs::read_to_string(dir.join("songs").join("grace.html"));
+        let playlist = fs::read_to_string(dir.join("playlists").join("sunday.html"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        let index = index.unwrap();
+        assert!(index.contains("<a href=\"songs/grace.html\">Grace</a>"));
+        assert!(index.contains("<a href=\"playlists/sunday.html\">Sunday</a>"));
+        assert!(page.unwrap().contains("data-chord=\"G\""));
+        assert!(playlist.unwrap().contains("<h1>Grace</h1>"));
+    }
+
+    #[test]
+    fn keeps_sounding_chords_for_capo_shapes() {
+        let capo = song("{title: Capo}\n{key: A}\n{capo: 2}\n[A]One [E]two");
+        let html = export(std::slice::from_ref(&capo), None);
+        assert!(html.contains("data-capo=\"2\" data-display=\"shapes\""));
+        assert!(html.contains("Capo 2 (shapes in <span class=\"shape-key\">G</span>)"));
+        assert!(html.contains("<span class=\"chord\" data-chord=\"A\">G</span>"));
+        assert!(html.contains("<span class=\"chord\" data-chord=\"E\">D</span>"));
+
+        let config = Config {
+            chord_display: ChordDisplay::Concert,
+            ..Config::default()
+        };
+        let html = String::from_utf8(super::export(&[capo], None, &config)).unwrap();
+        assert!(html.contains("data-display=\"concert\""));
+        assert!(!html.contains("shape-key\">"));
+        assert!(html.contains("<span class=\"chord\" data-chord=\"A\">A</span>"));
+    }
+}
@@ -0,0 +1,149 @@
+use super::{is_comment, sections};
+use crate::{
+    conf::Config,
+    parser::{Song, SongLine, SongString},
+};
+
+/// Shows the key of a song below its title, set with the `key` option of `\beginsong`
+const PREAMBLE: &str = r"\documentclass{article}
+\usepackage[utf8]{inputenc}
+\usepackage[T1]{fontenc}
+\usepackage[chorded]{songs}
+
+\newsongkey{key}{\def\songkey{}}{\def\songkey{Key: #1\par}}
+\renewcommand{\extendprelude}{\showrefs\showauthors{\bfseries\songkey}}
+";
+
+/// Exports songs as a LaTeX document for the `songs` package. When a title is given, the songs
+/// are put in a section with that title, in the order they are given.
+pub fn export(songs: &[Song], title: Option<&str>, config: &Config) -> Vec<u8> {
+    let mut tex = String::from(PREAMBLE);
+    tex += "\n\\begin{document}\n";
+    if let Some(title) = title {
+        tex += &format!("\\songsection{{{}}}\n", escape(title));
+    }
+    tex += "\\begin{songs}{}\n";
+    for song in songs {
+        tex += &self::song(song, config);
+    }
+    tex += "\\end{songs}\n\\end{document}\n";
+    tex.into_bytes()
+}
+
+fn song(song: &Song, config: &Config) -> String {
+    let mut options = vec![];
+    if !song.subtitle.is_empty() {
+        options.push(format!("by={{{}}}", escape(&song.subtitle)));
+    }
+    if let Some(key) = song.key {
+        options.push(format!("key={{{}}}", escape(&key.to_string())));
+    }
+    let mut tex = format!("\n\\beginsong{{{}}}", escape(&song.title));
+    if !options.is_empty() {
+        tex += &format!("[{}]", options.join(", "));
+    }
+    tex += "\n";
+    if song.capo > 0 {
+        tex += &format!("\\capo{{{}}}\n", song.capo);
+    }
+
+    let content = song.display(song.capo, config);
+    for section in sections(&content) {
+        let (begin, end) = match section[0].chorus {
+            true => ("\\beginchorus", "\\endchorus"),
+            // Sections of only comments are notes, which shouldn't take up a verse number
+            false if section.iter().all(|line| is_comment(line)) => ("\\beginverse*", "\\endverse"),
+            false => ("\\beginverse", "\\endverse"),
+        };
+        tex += begin;
+        tex += "\n";
+        for line in section {
+            tex += &self::line(line);
+            tex += "\n";
+        }
+        tex += end;
+        tex += "\n";
+    }
+    tex + "\\endsong\n"
+}
+
+fn line(line: &SongLine) -> String {
+    line.blocks
+        .iter()
+        .flat_map(|block| block.0.iter())
+        .map(|string| match string {
+            SongString::Chord(chord) => format!("\\[{}]", chord),
+            SongString::Text(text) => escape(text),
+            SongString::Annotation(annotation) => format!("\\[\\textit{{{}}}]", escape(annotation)),
+            SongString::CommentItalic(comment) => {
+                format!("\\textnote{{\\textit{{{}}}}}", escape(comment.trim()))
+            }
+            comment => format!("\\textnote{{{}}}", escape(comment.text().trim())),
+        })
+        .collect::<String>()
+        .trim_end()
+        .to_owned()
+}
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\\' => String::from("\\textbackslash{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => format!("\\{}", c),
+            '~' => String::from("\\textasciitilde{}"),
+            '^' => String::from("\\textasciicircum{}"),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(chordpro: &str, title: Option<&str>) -> String {
+        let song = Song::from(chordpro.to_owned());
+        String::from_utf8(super::export(&[song], title, &Config::default())).unwrap()
+    }
+
+    #[test]
+    fn exports_songs_for_the_songs_package() {
+        let tex = export(
+            "{title: Amazing Grace}\n{subtitle: John Newton}\n{key: G}\n\n\
+             A[G]mazing [C]grace\n\n{soc}\n[Em]I once was [D]lost\n{eoc}\n",
+            None,
+        );
+        assert!(tex.starts_with("\\documentclass{article}\n"));
+        assert!(tex.contains(
+            "\\beginsong{Amazing Grace}[by={John Newton}, key={G}]\n\
+             \\beginverse\nA\\[G]mazing \\[C]grace\n\\endverse\n\
+             \\beginchorus\n\\[Em]I once was \\[D]lost\n\\endchorus\n\\endsong\n"
+        ));
+    }
+
+    #[test]
+    fn exports_capo_and_notes() {
+        let tex = export(
+            "{title: Capo}\n{capo: 3}\n\n{c: Slowly}\n",
+            Some("Book & more"),
+        );
+        assert!(tex.contains("\\songsection{Book \\& more}\n"));
+        assert!(tex.contains("\\capo{3}\n"));
+        assert!(tex.contains("\\beginverse*\n\\textnote{Slowly}\n\\endverse\n"));
+    }
+
+    #[test]
+    fn escapes_special_characters() {
+        assert_eq!(escape("50% & #1_{x}"), "50\\% \\& \\#1\\_\\{x\\}");
+        assert_eq!(
+            escape("a\\b~^"),
+            "a\\textbackslash{}b\\textasciitilde{}\\textasciicircum{}"
+        );
+    }
+
+    #[test]
+    fn escapes_sharp_keys() {
+        let tex = export("{title: Sharp}\n{key: F#m}\n[F#m]Low", None);
+        assert!(tex.contains("\\beginsong{Sharp}[key={F\\#m}]\n"), "{}", tex);
+    }
+}
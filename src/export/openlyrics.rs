@@ -0,0 +1,135 @@
+use super::{escape, exported_lines, is_comment, is_recalled, line_text, section_text, sections};
+use crate::{
+    conf::Config,
+    parser::{Song, SongLine, SongString},
+};
+use std::collections::HashMap;
+
+/// Exports a song as OpenLyrics XML. Sections become verses named after their labels, like
+/// `v1`, `c1` and `b1`, with the chords as they sound.
+pub fn export(song: &Song, config: &Config) -> Vec<u8> {
+    let mut properties = format!(
+        "    <titles>\n      <title>{}</title>\n    </titles>\n",
+        escape(&song.title)
+    );
+    if !song.subtitle.is_empty() {
+        properties += &format!(
+            "    <authors>\n      <author>{}</author>\n    </authors>\n",
+            escape(&song.subtitle)
+        );
+    }
+    if let Some(key) = song.key {
+        properties += &format!("    <key>{}</key>\n", key);
+    }
+
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    let mut order = vec![];
+    // Sections repeated with {chorus} are only added to the order again
+    let mut ids: HashMap<String, String> = HashMap::new();
+    let mut lyrics = String::new();
+    for section in sections(&exported_lines(&song.content, &config.selection())) {
+        let text = section_text(&section);
+        if let Some(name) = ids.get(&text).filter(|_| is_recalled(&section)) {
+            order.push(name.clone());
+            continue;
+        }
+        // Labels like "Chorus" name the verse, other comments are kept in the lyrics
+        let label = match is_comment(section[0]) {
+            true => kind(&line_text(section[0])),
+            false => None,
+        };
+        let lines = match label {
+            Some(_) => &section[1..],
+            None => &section[..],
+        };
+        if lines.is_empty() {
+            continue;
+        }
+        let kind = label.unwrap_or(match lines[0].chorus {
+            true => 'c',
+            false => 'v',
+        });
+        let count = counts.entry(kind).or_insert(0);
+        *count += 1;
+        let name = format!("{}{}", kind, count);
+
+        lyrics += &format!("    <verse name=\"{}\">\n      <lines>", name);
+        let lines: Vec<String> = lines.iter().map(|line| self::line(line)).collect();
+        lyrics += &lines.join("<br/>\n        ");
+        lyrics += "</lines>\n    </verse>\n";
+        ids.entry(text).or_insert_with(|| name.clone());
+        order.push(name);
+    }
+    properties += &format!("    <verseOrder>{}</verseOrder>\n", order.join(" "));
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <song xmlns=\"http://openlyrics.info/namespace/2009/song\" version=\"0.9\" \
+         createdIn=\"gpro\" modifiedIn=\"gpro\">\n  <properties>\n{}  </properties>\n  \
+         <lyrics>\n{}  </lyrics>\n</song>\n",
+        properties, lyrics
+    )
+    .into_bytes()
+}
+
+/// Finds the kind of verse from a section label, like `c` for "Chorus 2"
+fn kind(label: &str) -> Option<char> {
+    let label = label.to_lowercase();
+    let word = label.split_whitespace().next().unwrap_or_default();
+    match word.trim_end_matches(':') {
+        "verse" => Some('v'),
+        "chorus" | "refrain" => Some('c'),
+        "bridge" => Some('b'),
+        "pre-chorus" | "prechorus" => Some('p'),
+        "intro" => Some('i'),
+        "outro" | "ending" => Some('e'),
+        _ => None,
+    }
+}
+
+fn line(line: &SongLine) -> String {
+    line.blocks
+        .iter()
+        .flat_map(|block| block.0.iter())
+        .map(|string| match string {
+            SongString::Chord(chord) => format!("<chord name=\"{}\"/>", escape(chord)),
+            SongString::Text(text) => escape(text),
+            comment => format!("<comment>{}</comment>", escape(comment.text())),
+        })
+        .collect::<String>()
+        .trim_end()
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_sections_as_verses() {
+        let song = Song::from(String::from(
+            "{title: Grace & Glory}\n{subtitle: Newton}\n{key: G}\n\n\
+             {c: Verse 1}\nA[G]mazing grace\nHow sweet\n\n{soc}\n[Em]I once was lost\n{eoc}\n",
+        ));
+        let xml = String::from_utf8(export(&song, &Config::default())).unwrap();
+        assert!(xml.contains("<title>Grace &amp; Glory</title>"));
+        assert!(xml.contains("<author>Newton</author>"));
+        assert!(xml.contains("<key>G</key>"));
+        assert!(xml.contains("<verseOrder>v1 c1</verseOrder>"));
+        assert!(xml.contains(
+            "<verse name=\"v1\">\n      <lines>A<chord name=\"G\"/>mazing grace<br/>\n        \
+             How sweet</lines>"
+        ));
+        assert!(
+            xml.contains("<verse name=\"c1\">\n      <lines><chord name=\"Em\"/>I once was lost")
+        );
+    }
+
+    #[test]
+    fn reads_verse_kinds_from_labels() {
+        assert_eq!(kind("Chorus 2"), Some('c'));
+        assert_eq!(kind("Pre-Chorus:"), Some('p'));
+        assert_eq!(kind("Refrain"), Some('c'));
+        assert_eq!(kind("Slowly"), None);
+    }
+}
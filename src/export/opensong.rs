@@ -0,0 +1,146 @@
+use super::{escape, exported_lines, is_comment, is_recalled, line_text, section_text, sections};
+use crate::{
+    conf::Config,
+    parser::{Song, SongLine, SongString},
+};
+use std::collections::HashMap;
+
+/// Exports a song as an OpenSong song, with a chord line above every lyric line
+pub fn export(song: &Song, config: &Config) -> Vec<u8> {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    let mut order = vec![];
+    // Sections repeated with {chorus} are only added to the order again
+    let mut ids: HashMap<String, String> = HashMap::new();
+    let mut lyrics = vec![];
+    for section in sections(&exported_lines(&song.content, &config.selection())) {
+        let text = section_text(&section);
+        if let Some(id) = ids.get(&text).filter(|_| is_recalled(&section)) {
+            order.push(id.clone());
+            continue;
+        }
+        let kind = match is_comment(section[0]) {
+            true => kind(&line_text(section[0])),
+            false => None,
+        };
+        let lines = match kind {
+            Some(_) => &section[1..],
+            None => &section[..],
+        };
+        if lines.is_empty() {
+            continue;
+        }
+        let kind = kind.unwrap_or(match lines[0].chorus {
+            true => 'C',
+            false => 'V',
+        });
+        let count = counts.entry(kind).or_insert(0);
+        *count += 1;
+        let id = format!("{}{}", kind, count);
+
+        lyrics.push(format!("[{}]", id));
+        lines
+            .iter()
+            .for_each(|line| lyrics.append(&mut self::line(line)));
+        lyrics.push(String::new());
+        ids.entry(text).or_insert_with(|| id.clone());
+        order.push(id);
+    }
+
+    let mut fields = vec![format!("  <title>{}</title>", escape(&song.title))];
+    if !song.subtitle.is_empty() {
+        fields.push(format!("  <author>{}</author>", escape(&song.subtitle)));
+    }
+    if let Some(key) = song.key {
+        fields.push(format!("  <key>{}</key>", key));
+    }
+    if song.capo > 0 {
+        fields.push(format!("  <capo print=\"true\">{}</capo>", song.capo));
+    }
+    fields.push(format!(
+        "  <presentation>{}</presentation>",
+        order.join(" ")
+    ));
+    fields.push(format!(
+        "  <lyrics>{}</lyrics>",
+        escape(lyrics.join("\n").trim_end())
+    ));
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<song>\n{}\n</song>\n",
+        fields.join("\n")
+    )
+    .into_bytes()
+}
+
+/// Finds the section marker for a section label, like `C` for "Chorus 2"
+fn kind(label: &str) -> Option<char> {
+    let label = label.to_lowercase();
+    let word = label.split_whitespace().next().unwrap_or_default();
+    match word.trim_end_matches(':') {
+        "verse" => Some('V'),
+        "chorus" | "refrain" => Some('C'),
+        "bridge" => Some('B'),
+        "pre-chorus" | "prechorus" => Some('P'),
+        "tag" => Some('T'),
+        "intro" => Some('I'),
+        "outro" | "ending" => Some('E'),
+        _ => None,
+    }
+}
+
+/// Writes a line as a chord line starting with `.` above a lyric line starting with a space.
+/// Lines of only comments start with `;`.
+fn line(line: &SongLine) -> Vec<String> {
+    if is_comment(line) {
+        return vec![format!(";{}", line_text(line))];
+    }
+    let mut chords = String::new();
+    let mut lyrics = String::new();
+    for string in line.blocks.iter().flat_map(|block| block.0.iter()) {
+        match string {
+            SongString::Chord(chord) | SongString::Annotation(chord) => {
+                let column = lyrics.chars().count();
+                let taken = chords.chars().count();
+                // Chords that don't fit above the lyrics push the lyrics aside
+                if taken > column {
+                    lyrics += &" ".repeat(taken - column);
+                }
+                chords += &" ".repeat(column.max(taken) - taken);
+                chords += chord;
+                chords.push(' ');
+            }
+            string => lyrics += string.text(),
+        }
+    }
+    let mut rows = vec![];
+    if !chords.trim().is_empty() {
+        rows.push(format!(".{}", chords.trim_end()));
+    }
+    rows.push(format!(" {}", lyrics.trim_end()));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_chord_lines_above_lyrics() {
+        let song = Song::from(String::from(
+            "{title: Grace}\n{key: G}\n{capo: 2}\n\n{c: Verse}\nA[G]mazing [C]grace\n{c: Slowly}\n\n\
+             {soc}\n[Em]I [D]once\n{eoc}\n",
+        ));
+        let xml = String::from_utf8(export(&song, &Config::default())).unwrap();
+        assert!(xml.contains("  <key>G</key>\n  <capo print=\"true\">2</capo>\n"));
+        assert!(xml.contains("<presentation>V1 C1</presentation>"));
+        assert!(xml.contains(
+            "<lyrics>[V1]\n. G      C\n Amazing grace\n;Slowly\n\n[C1]\n.Em D\n I  once</lyrics>"
+        ));
+    }
+
+    #[test]
+    fn pushes_lyrics_aside_for_long_chords() {
+        let song = Song::from(String::from("[Cmaj7]A [G]b"));
+        assert_eq!(line(&song.content[0]), [".Cmaj7 G", " A     b"]);
+    }
+}
@@ -0,0 +1,480 @@
+use super::{details, rgb};
+use crate::{
+    conf::{ChordDisplay, Config},
+    diagram::{self, Fingering},
+    parser::{display_width, Song, SongLine},
+};
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use tui::{
+    style::{Modifier, Style},
+    text::Spans,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+const TITLE_SIZE: f32 = 18.0;
+const SUBTITLE_SIZE: f32 = 12.0;
+const FONT_SIZE: f32 = 11.0;
+const SMALL_FONT_SIZE: f32 = 8.0;
+const LINE_HEIGHT: f32 = 13.0;
+/// Every glyph of the Courier fonts is 600 units wide, which keeps chords aligned over lyrics
+const CHAR_WIDTH: f32 = FONT_SIZE * 0.6;
+const CHORUS_INDENT: f32 = 12.0;
+const STRING_SPACING: f32 = 6.0;
+const FRET_SPACING: f32 = 8.0;
+const DIAGRAM_FRETS: u8 = 4;
+const DIAGRAM_CELL_WIDTH: f32 = 56.0;
+const DIAGRAM_CELL_HEIGHT: f32 = 64.0;
+
+/// Base fonts, which are named F1 to F6 in the page resources
+const FONTS: [&str; 6] = [
+    "Courier",
+    "Courier-Bold",
+    "Courier-Oblique",
+    "Courier-BoldOblique",
+    "Helvetica",
+    "Helvetica-Bold",
+];
+
+pub fn export(songs: &[Song], title: Option<&str>, config: &Config) -> Vec<u8> {
+    let mut document = Document::default();
+    let mut start_pages = vec![];
+    for song in songs {
+        start_pages.push(document.pages.len());
+        document.song(song, config);
+    }
+
+    let mut pages = match title.is_some() || songs.len() > 1 {
+        true => {
+            let entries: Vec<(&str, usize)> = songs
+                .iter()
+                .map(|song| song.title.as_str())
+                .zip(start_pages)
+                .collect();
+            let mut contents = Document::default();
+            contents.table_of_contents(title.unwrap_or("Contents"), &entries, config);
+            contents.pages.append(&mut document.pages);
+            contents.pages
+        }
+        false => document.pages,
+    };
+
+    let page_count = pages.len();
+    for (i, page) in pages.iter_mut().enumerate() {
+        let number = (i + 1).to_string();
+        let x = (PAGE_WIDTH - number.len() as f32 * SMALL_FONT_SIZE * 0.6) / 2.0;
+        write_text(
+            page,
+            x,
+            MARGIN / 2.0,
+            b"F1",
+            SMALL_FONT_SIZE,
+            Style::default(),
+            &number,
+        );
+    }
+
+    write_pdf(
+        pages,
+        title.or_else(|| songs.first().map(|song| song.title.as_str())),
+        page_count,
+    )
+}
+
+fn write_pdf(pages: Vec<Content>, title: Option<&str>, page_count: usize) -> Vec<u8> {
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let info_id = Ref::new(3);
+    let font_ids: Vec<Ref> = (0..FONTS.len() as i32).map(|i| Ref::new(4 + i)).collect();
+    let first_page = 4 + FONTS.len() as i32;
+    let page_ids: Vec<Ref> = (0..page_count as i32)
+        .map(|i| Ref::new(first_page + 2 * i))
+        .collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id)
+        .kids(page_ids.iter().copied())
+        .count(page_count as i32);
+    if let Some(title) = title {
+        pdf.document_info(info_id).title(TextStr(title));
+    }
+    for (font, id) in FONTS.iter().zip(&font_ids) {
+        pdf.type1_font(*id)
+            .base_font(Name(font.as_bytes()))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+    }
+
+    for (page_id, content) in page_ids.iter().zip(pages) {
+        let content_id = Ref::new(page_id.get() + 1);
+        let mut page = pdf.page(*page_id);
+        page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+            .parent(page_tree_id)
+            .contents(content_id);
+        let mut resources = page.resources();
+        let mut fonts = resources.fonts();
+        for (i, id) in font_ids.iter().enumerate() {
+            fonts.pair(Name(format!("F{}", i + 1).as_bytes()), *id);
+        }
+        fonts.finish();
+        resources.finish();
+        page.finish();
+        pdf.stream(content_id, &content.finish());
+    }
+    pdf.finish()
+}
+
+/// Pages that are laid out from top to bottom
+#[derive(Default)]
+struct Document {
+    pages: Vec<Content>,
+    /// Height of the current line on the last page
+    y: f32,
+}
+
+impl Document {
+    fn new_page(&mut self) {
+        self.pages.push(Content::new());
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    /// Starts a new page if there isn't enough room left for the given height
+    fn reserve(&mut self, height: f32) {
+        if self.pages.is_empty() || self.y - height < MARGIN {
+            self.new_page()
+        }
+    }
+
+    fn page(&mut self) -> &mut Content {
+        self.pages.last_mut().unwrap()
+    }
+
+    fn text(&mut self, x: f32, font: &[u8], size: f32, style: Style, text: &str) {
+        let y = self.y;
+        write_text(self.page(), x, y, font, size, style, text);
+    }
+
+    fn spans(&mut self, x: f32, spans: &Spans) {
+        let mut x = x;
+        for span in spans.0.iter() {
+            if !span.content.trim().is_empty() {
+                self.text(x, courier(span.style), FONT_SIZE, span.style, &span.content);
+            }
+            x += display_width(&span.content) as f32 * CHAR_WIDTH;
+        }
+    }
+
+    fn song(&mut self, song: &Song, config: &Config) {
+        self.new_page();
+        let title_style = config.theme.title.to_style();
+        let title_font: &[u8] = match title_style.add_modifier.contains(Modifier::BOLD) {
+            true => b"F6",
+            false => b"F5",
+        };
+        self.y -= TITLE_SIZE;
+        self.text(MARGIN, title_font, TITLE_SIZE, title_style, &song.title);
+        if !song.subtitle.is_empty() {
+            self.y -= SUBTITLE_SIZE * 1.5;
+            self.text(
+                MARGIN,
+                b"F5",
+                SUBTITLE_SIZE,
+                Style::default(),
+                &song.subtitle,
+            );
+        }
+
+        let details = details(song);
+        if !details.is_empty() {
+            self.y -= LINE_HEIGHT * 1.5;
+            self.text(
+                MARGIN,
+                b"F1",
+                FONT_SIZE,
+                Style::default(),
+                &details.join("   "),
+            );
+        }
+
+        // Diagrams show the shapes to play, whichever chords are displayed
+        let shapes = song.with_capo(song.capo, ChordDisplay::Shapes);
+        let mut chords: Vec<&str> = vec![];
+        shapes.iter().flat_map(SongLine::chords).for_each(|chord| {
+            if !chords.contains(&chord) {
+                chords.push(chord)
+            }
+        });
+        self.diagrams(&chords);
+        self.y -= LINE_HEIGHT;
+
+        let content = song.display(song.capo, config);
+        let max_width = ((PAGE_WIDTH - 2.0 * MARGIN - CHORUS_INDENT) / CHAR_WIDTH) as usize;
+        for line in content.iter().flat_map(|line| line.wrap(max_width)) {
+            // Pages have a single column, so column breaks start a new page too
+            if line.break_before.is_some() {
+                self.new_page();
+            }
+            // The chorus bar is drawn as a line instead
+            let rows = SongLine {
+                chorus: false,
+                ..line.clone()
+            }
+            .format(&config.theme);
+            self.reserve(rows.len() as f32 * LINE_HEIGHT);
+            for row in rows.iter() {
+                self.y -= LINE_HEIGHT;
+                match line.chorus {
+                    true => {
+                        let (x, y) = (MARGIN + 3.0, self.y - 3.0);
+                        let (r, g, b) = rgb(config.theme.comment.fg);
+                        self.page()
+                            .set_stroke_rgb(r, g, b)
+                            .set_line_width(1.5)
+                            .move_to(x, y)
+                            .line_to(x, y + LINE_HEIGHT)
+                            .stroke();
+                        self.spans(MARGIN + CHORUS_INDENT, row);
+                    }
+                    false => self.spans(MARGIN, row),
+                }
+            }
+        }
+    }
+
+    fn diagrams(&mut self, chords: &[&str]) {
+        let fingerings: Vec<(&str, Fingering)> = chords
+            .iter()
+            .filter_map(|chord| diagram::guitar(chord).map(|fingering| (*chord, fingering)))
+            .collect();
+        let per_row = ((PAGE_WIDTH - 2.0 * MARGIN) / DIAGRAM_CELL_WIDTH) as usize;
+        for row in fingerings.chunks(per_row) {
+            self.reserve(DIAGRAM_CELL_HEIGHT);
+            self.y -= DIAGRAM_CELL_HEIGHT;
+            for (i, (chord, fingering)) in row.iter().enumerate() {
+                let x = MARGIN + i as f32 * DIAGRAM_CELL_WIDTH;
+                self.diagram(x, self.y + DIAGRAM_CELL_HEIGHT, chord, fingering);
+            }
+        }
+    }
+
+    /// Draws a chord diagram with its top left corner at the given position
+    fn diagram(&mut self, x: f32, top: f32, chord: &str, fingering: &Fingering) {
+        let grid_width = 5.0 * STRING_SPACING;
+        let name_width = chord.chars().count() as f32 * FONT_SIZE * 0.6;
+        let name_x = x + (grid_width - name_width) / 2.0;
+        write_text(
+            self.page(),
+            name_x,
+            top - FONT_SIZE,
+            b"F2",
+            FONT_SIZE,
+            Style::default(),
+            chord,
+        );
+
+        let highest = fingering.iter().flatten().max().copied().unwrap_or(0);
+        let lowest = fingering.iter().flatten().filter(|fret| **fret > 0).min();
+        let base_fret = match highest <= DIAGRAM_FRETS {
+            true => 1,
+            false => *lowest.unwrap_or(&1),
+        };
+
+        let grid_top = top - FONT_SIZE - 12.0;
+        let grid_bottom = grid_top - DIAGRAM_FRETS as f32 * FRET_SPACING;
+        let page = self.page();
+        page.set_stroke_rgb(0.0, 0.0, 0.0)
+            .set_fill_rgb(0.0, 0.0, 0.0)
+            .set_line_width(0.5);
+        for string in 0..6 {
+            let string_x = x + string as f32 * STRING_SPACING;
+            page.move_to(string_x, grid_top)
+                .line_to(string_x, grid_bottom);
+        }
+        for fret in 0..=DIAGRAM_FRETS {
+            let fret_y = grid_top - fret as f32 * FRET_SPACING;
+            page.move_to(x, fret_y).line_to(x + grid_width, fret_y);
+        }
+        page.stroke();
+        if base_fret == 1 {
+            page.set_line_width(2.0)
+                .move_to(x, grid_top)
+                .line_to(x + grid_width, grid_top)
+                .stroke();
+        } else {
+            let label = format!("{}fr", base_fret);
+            write_text(
+                page,
+                x + grid_width + 3.0,
+                grid_top - FRET_SPACING + 2.0,
+                b"F1",
+                SMALL_FONT_SIZE,
+                Style::default(),
+                &label,
+            );
+        }
+
+        for (string, fret) in fingering.iter().enumerate() {
+            let string_x = x + string as f32 * STRING_SPACING;
+            match fret {
+                None => write_text(
+                    page,
+                    string_x - 2.0,
+                    grid_top + 2.0,
+                    b"F1",
+                    SMALL_FONT_SIZE,
+                    Style::default(),
+                    "x",
+                ),
+                Some(0) => {
+                    page.set_line_width(0.5);
+                    circle(page, string_x, grid_top + 4.0, 1.8);
+                    page.stroke();
+                }
+                Some(fret) => {
+                    let fret_y = grid_top - (fret + 1 - base_fret) as f32 * FRET_SPACING
+                        + FRET_SPACING / 2.0;
+                    circle(page, string_x, fret_y, 2.4);
+                    page.fill_nonzero();
+                }
+            }
+        }
+    }
+
+    fn table_of_contents(&mut self, title: &str, entries: &[(&str, usize)], config: &Config) {
+        let rows_per_page =
+            ((PAGE_HEIGHT - 2.0 * MARGIN - TITLE_SIZE * 2.0) / LINE_HEIGHT).floor() as usize;
+        let pages = entries.len().div_ceil(rows_per_page);
+        let max_width = ((PAGE_WIDTH - 2.0 * MARGIN) / CHAR_WIDTH) as usize;
+        let title_style = config.theme.title.to_style();
+        for (i, page_entries) in entries.chunks(rows_per_page).enumerate() {
+            self.new_page();
+            if i == 0 {
+                self.y -= TITLE_SIZE;
+                self.text(MARGIN, b"F6", TITLE_SIZE, title_style, title);
+            }
+            self.y -= TITLE_SIZE;
+            for (song, start_page) in page_entries {
+                let number = (start_page + pages + 1).to_string();
+                let mut name: String = song.chars().take(max_width - number.len() - 2).collect();
+                let dots = max_width - name.chars().count() - number.len();
+                name += &format!(" {} {}", ".".repeat(dots.saturating_sub(2)), number);
+                self.y -= LINE_HEIGHT;
+                self.text(MARGIN, b"F1", FONT_SIZE, Style::default(), &name);
+            }
+        }
+    }
+}
+
+fn write_text(
+    page: &mut Content,
+    x: f32,
+    y: f32,
+    font: &[u8],
+    size: f32,
+    style: Style,
+    text: &str,
+) {
+    let (r, g, b) = rgb(style.fg);
+    page.set_fill_rgb(r, g, b)
+        .begin_text()
+        .set_font(Name(font), size)
+        .next_line(x, y)
+        .show(Str(&encode(text)))
+        .end_text();
+}
+
+/// Picks the Courier font that matches the boldness and slant of a style
+fn courier(style: Style) -> &'static [u8] {
+    match (
+        style.add_modifier.contains(Modifier::BOLD),
+        style.add_modifier.contains(Modifier::ITALIC),
+    ) {
+        (false, false) => b"F1",
+        (true, false) => b"F2",
+        (false, true) => b"F3",
+        (true, true) => b"F4",
+    }
+}
+
+/// Encodes text for the WinAnsi encoding of the base fonts, with a glyph for every column the
+/// text takes up in the terminal so it lines up like `SongLine::format` pads it. Accents that
+/// aren't part of a character are left out, unsupported characters become `?` and wide
+/// characters are followed by a space.
+fn encode(text: &str) -> Vec<u8> {
+    text.graphemes(true)
+        .flat_map(|grapheme| {
+            let width = display_width(grapheme);
+            let glyph = match grapheme.chars().next() {
+                Some(c @ (' '..='~' | '\u{a0}'..='\u{ff}')) => c as u8,
+                Some('€') => 0x80,
+                Some('…') => 0x85,
+                Some('‘') => 0x91,
+                Some('’') => 0x92,
+                Some('“') => 0x93,
+                Some('”') => 0x94,
+                Some('–') => 0x96,
+                Some('—') => 0x97,
+                _ => b'?',
+            };
+            std::iter::once(glyph)
+                .chain(std::iter::repeat(b' '))
+                .take(width)
+        })
+        .collect()
+}
+
+/// Adds a circle to the current path, approximated with Bézier curves
+fn circle(page: &mut Content, x: f32, y: f32, r: f32) {
+    let k = r * 0.552_284_8;
+    page.move_to(x + r, y)
+        .cubic_to(x + r, y + k, x + k, y + r, x, y + r)
+        .cubic_to(x - k, y + r, x - r, y + k, x - r, y)
+        .cubic_to(x - r, y - k, x - k, y - r, x, y - r)
+        .cubic_to(x + k, y - r, x + r, y - k, x + r, y);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(title: &str) -> Song {
+        Song::from(format!(
+            "{{title: {}}}\n{{key: G}}\n\nA[G]mazing [C]grace\n",
+            title
+        ))
+    }
+
+    fn pages(pdf: &[u8]) -> usize {
+        String::from_utf8_lossy(pdf)
+            .matches("/Type /Page\n")
+            .count()
+    }
+
+    #[test]
+    fn exports_songs() {
+        let pdf = export(&[song("Amazing Grace")], None, &Config::default());
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF"));
+        assert!(text.contains("(Amazing Grace) Tj"));
+        assert!(text.contains("(grace) Tj"));
+        assert_eq!(pages(&pdf), 1);
+    }
+
+    #[test]
+    fn exports_playlists_with_contents() {
+        let songs = [song("First"), song("Second")];
+        let pdf = export(&songs, Some("Sunday"), &Config::default());
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("(Sunday) Tj"));
+        assert_eq!(pages(&pdf), 3);
+    }
+
+    #[test]
+    fn encodes_text_as_win_ansi() {
+        assert_eq!(encode("Café"), b"Caf\xe9");
+        assert_eq!(encode("€"), b"\x80");
+        assert_eq!(encode("♯"), b"?");
+    }
+}
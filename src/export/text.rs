@@ -0,0 +1,165 @@
+use super::{details, is_comment, sections};
+use crate::{
+    conf::{Config, Theme},
+    parser::{display_width, Song, SongLine},
+};
+
+/// Exports songs as monospace plain text with chords above the lyrics
+pub fn plain(songs: &[Song], title: Option<&str>, width: usize, config: &Config) -> Vec<u8> {
+    let mut text = String::new();
+    if let Some(title) = title {
+        text += &format!("{}\n{}\n\n", title, "=".repeat(display_width(title)));
+        for (i, song) in songs.iter().enumerate() {
+            text += &format!("{}. {}\n", i + 1, song.title);
+        }
+        text += "\n";
+    }
+    let songs: Vec<String> = songs
+        .iter()
+        .map(|song| {
+            let mut text = song.title.clone() + "\n";
+            if !song.subtitle.is_empty() {
+                text += &format!("{}\n", song.subtitle);
+            }
+            let details = details(song);
+            if !details.is_empty() {
+                text += &format!("{}\n", details.join("   "));
+            }
+            text += "\n";
+            for line in song.display(song.capo, config) {
+                for row in rows(&line, width) {
+                    text += &format!("{}\n", row);
+                }
+            }
+            text
+        })
+        .collect();
+    (text + &songs.join("\n\n")).into_bytes()
+}
+
+/// Exports songs as Markdown, with a fenced code block for every section so the chords stay
+/// aligned
+pub fn markdown(songs: &[Song], title: Option<&str>, width: usize, config: &Config) -> Vec<u8> {
+    let mut text = String::new();
+    let heading = match title {
+        Some(title) => {
+            text += &format!("# {}\n\n", title);
+            "##"
+        }
+        None => "#",
+    };
+    let songs: Vec<String> = songs
+        .iter()
+        .map(|song| {
+            let mut text = format!("{} {}\n\n", heading, song.title);
+            if !song.subtitle.is_empty() {
+                text += &format!("*{}*\n\n", song.subtitle);
+            }
+            let details = details(song);
+            if !details.is_empty() {
+                text += &format!("{}\n\n", details.join(" · "));
+            }
+            let content = song.display(song.capo, config);
+            for mut section in sections(&content) {
+                let label = match section.first() {
+                    Some(line) if is_comment(line) => {
+                        let label = rows(line, usize::MAX).join(" ");
+                        section.remove(0);
+                        Some(label.trim().to_owned())
+                    }
+                    Some(line) if line.chorus => Some(String::from("Chorus")),
+                    _ => None,
+                };
+                if let Some(label) = label {
+                    text += &format!("**{}**\n\n", label);
+                }
+                if section.is_empty() {
+                    continue;
+                }
+                text += "```\n";
+                for line in section {
+                    // Choruses are labeled, so they don't need the chorus bar
+                    for row in rows(&SongLine::from(line.blocks.clone(), false), width) {
+                        text += &format!("{}\n", row);
+                    }
+                }
+                text += "```\n\n";
+            }
+            text
+        })
+        .collect();
+    (text + songs.join("\n").trim_end() + "\n").into_bytes()
+}
+
+/// Formats a line as unstyled rows of text, wrapped to the given width
+fn rows(line: &SongLine, width: usize) -> Vec<String> {
+    line.wrap(width)
+        .iter()
+        .flat_map(|line| line.format(&Theme::default()))
+        .map(|spans| {
+            spans
+                .0
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect::<String>()
+                .trim_end()
+                .to_owned()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SONG: &str = "{title: Amazing Grace}\n{subtitle: John Newton}\n{key: G}\n{capo: 2}\n\n\
+                        A[G]mazing [C]grace\n\n{c: Verse 2}\n[G]Through many [D]dangers\n\n\
+                        {soc}\n[Em]I once was [D]lost\n{eoc}\n";
+
+    fn config() -> Config {
+        Config {
+            chord_display: crate::conf::ChordDisplay::Concert,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn exports_plain_text() {
+        let song = Song::from(SONG.to_owned());
+        let text = String::from_utf8(plain(&[song], None, 80, &config())).unwrap();
+        assert!(text.starts_with("Amazing Grace\nJohn Newton\nKey: G   Capo 2 (F shapes)\n\n"));
+        assert!(text.contains(" G      C\nAmazing grace\n"));
+        assert!(text.contains("| Em         D\n| I once was lost"));
+    }
+
+    #[test]
+    fn lists_the_songs_of_a_book() {
+        let songs = [
+            Song::from(SONG.to_owned()),
+            Song::from(String::from("{title: Two}")),
+        ];
+        let text = String::from_utf8(plain(&songs, Some("Sunday"), 80, &config())).unwrap();
+        assert!(text.starts_with("Sunday\n======\n\n1. Amazing Grace\n2. Two\n\n"));
+    }
+
+    #[test]
+    fn exports_markdown_sections() {
+        let song = Song::from(SONG.to_owned());
+        let text = String::from_utf8(markdown(&[song], None, 80, &config())).unwrap();
+        assert!(text.starts_with("# Amazing Grace\n\n*John Newton*\n\n"));
+        assert!(text.contains("```\n G      C\nAmazing grace\n```\n"));
+        assert!(text.contains("**Verse 2**\n\n```\nG            D\nThrough many dangers\n```"));
+        assert!(text.contains("**Chorus**\n\n```\nEm         D\nI once was lost\n```"));
+    }
+
+    #[test]
+    fn wraps_to_the_width() {
+        let song = Song::from(String::from("[C]Words that go on [G]and on and on"));
+        let text = String::from_utf8(plain(&[song], None, 20, &config())).unwrap();
+        assert!(
+            text.lines().all(|line| line.chars().count() <= 20),
+            "{}",
+            text
+        );
+    }
+}
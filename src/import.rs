@@ -0,0 +1,122 @@
+mod guitarpro;
+mod musicxml;
+mod openlyrics;
+mod opensong;
+mod songbook;
+mod text;
+
+use std::{error::Error, path::Path, str::FromStr};
+
+/// The formats songs can be imported from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Plain text with chords on the line above the lyrics, as found on Ultimate Guitar
+    Text,
+    OpenLyrics,
+    OpenSong,
+    /// Guitar Pro 3, 4 and 5 files
+    GuitarPro,
+    /// MusicXML scores, compressed or not
+    MusicXml,
+    /// Backups of Linkesoft SongBook, with ChordPro songs and sets
+    SongBook,
+    /// Backups of SongBook Pro, ending in `.sbp`
+    SongBookPro,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "txt" | "text" => Ok(Format::Text),
+            "openlyrics" => Ok(Format::OpenLyrics),
+            "opensong" => Ok(Format::OpenSong),
+            "gp" | "guitarpro" => Ok(Format::GuitarPro),
+            "musicxml" | "mxl" => Ok(Format::MusicXml),
+            "songbook" => Ok(Format::SongBook),
+            "sbp" | "songbookpro" => Ok(Format::SongBookPro),
+            _ => Err(format!("Unknown import format '{}'", format)),
+        }
+    }
+}
+
+impl Format {
+    /// Guesses the format of a file from its extension and contents. OpenSong files usually
+    /// have no extension, while OpenLyrics files are told apart by their namespace.
+    pub fn detect(path: &Path, contents: &[u8]) -> Self {
+        let xml = String::from_utf8_lossy(&contents[..contents.len().min(512)]);
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("gp3" | "gp4" | "gp5") => Format::GuitarPro,
+            Some("musicxml" | "mxl") => Format::MusicXml,
+            Some("sbp") => Format::SongBookPro,
+            Some("zip") => Format::SongBook,
+            Some("xml") if xml.contains("<score-partwise") => Format::MusicXml,
+            Some("xml") | None if xml.contains("openlyrics.info") => Format::OpenLyrics,
+            Some("xml") | None if xml.contains("<song") => Format::OpenSong,
+            _ => Format::Text,
+        }
+    }
+
+    /// Whether files in this format hold a whole library instead of a single song
+    pub fn is_library(self) -> bool {
+        matches!(self, Format::SongBook | Format::SongBookPro)
+    }
+}
+
+/// The songs and playlists of a library backup, in ChordPro and the playlist format
+pub struct Backup {
+    pub songs: Vec<String>,
+    pub playlists: Vec<String>,
+    /// Entries of sets whose song isn't in the backup, as the name of the set and the entry
+    pub missing: Vec<(String, String)>,
+}
+
+/// Converts the contents of a file into a song in ChordPro format
+pub fn import(contents: &[u8], format: Format) -> Result<String, Box<dyn Error>> {
+    match format {
+        Format::Text => Ok(text::import(&String::from_utf8_lossy(contents))),
+        Format::OpenLyrics => openlyrics::import(&String::from_utf8_lossy(contents)),
+        Format::OpenSong => opensong::import(&String::from_utf8_lossy(contents)),
+        Format::GuitarPro => guitarpro::import(contents),
+        Format::MusicXml => musicxml::import(contents),
+        Format::SongBook | Format::SongBookPro => {
+            Err(Box::from("Backups hold a whole library, not a single song"))
+        }
+    }
+}
+
+/// Reads the songs and sets of a library backup
+pub fn import_library(contents: &[u8], format: Format) -> Result<Backup, Box<dyn Error>> {
+    match format {
+        Format::SongBook => songbook::linkesoft(contents),
+        Format::SongBookPro => songbook::songbook_pro(contents),
+        _ => Ok(Backup {
+            songs: vec![import(contents, format)?],
+            playlists: vec![],
+            missing: vec![],
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_formats() {
+        let detect =
+            |path: &str, contents: &str| Format::detect(Path::new(path), contents.as_bytes());
+        assert!(detect("song.txt", "<song>") == Format::Text);
+        assert!(detect("Amazing Grace", "<?xml?>\n<song>") == Format::OpenSong);
+        assert!(
+            detect("song.xml", "<song xmlns=\"http://openlyrics.info/\">") == Format::OpenLyrics
+        );
+        assert!(detect("notes", "Just some words") == Format::Text);
+        assert!(detect("lead.mxl", "PK") == Format::MusicXml);
+        assert!(detect("backup.sbp", "PK") == Format::SongBookPro);
+        assert!(detect("backup.zip", "PK") == Format::SongBook);
+        assert!(Format::SongBook.is_library() && !Format::OpenSong.is_library());
+        assert!(detect("lead.xml", "<score-partwise version=\"4.0\">") == Format::MusicXml);
+    }
+}
@@ -0,0 +1,872 @@
+extern crate rust_music_theory as rustmt;
+
+use crate::key::{Key, Mode};
+use rustmt::note::PitchClass;
+use std::error::Error;
+
+/// Ticks in a quarter note, used to line up beats of different tracks
+const QUARTER: u32 = 960;
+/// Width of the tab systems, in characters
+const TAB_WIDTH: usize = 80;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Reads the little-endian binary format of Guitar Pro 3, 4 and 5
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+    /// Version as a number like 306, 406 or 510
+    version: u32,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + count)
+            .ok_or("Unexpected end of Guitar Pro file")?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, count: usize) -> Result<()> {
+        self.bytes(count).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn i8(&mut self) -> Result<i8> {
+        Ok(self.u8()? as i8)
+    }
+
+    fn bool(&mut self) -> Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn i16(&mut self) -> Result<i16> {
+        let bytes = self.bytes(2)?;
+        Ok(i16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        let bytes = self.bytes(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Reads a string stored in a fixed number of bytes, preceded by its length
+    fn byte_size_string(&mut self, size: usize) -> Result<String> {
+        let length = self.u8()? as usize;
+        let bytes = self.bytes(size)?;
+        Ok(decode(&bytes[..length.min(size)]))
+    }
+
+    /// Reads a string preceded by its length as an int
+    fn int_size_string(&mut self) -> Result<String> {
+        let length = self.i32()?.max(0) as usize;
+        Ok(decode(self.bytes(length)?))
+    }
+
+    /// Reads a string preceded by its size plus one as an int and its length as a byte
+    fn int_byte_size_string(&mut self) -> Result<String> {
+        let size = self.i32()?.max(1) as usize;
+        let length = self.u8()? as usize;
+        let bytes = self.bytes(size - 1)?;
+        Ok(decode(&bytes[..length.min(size - 1)]))
+    }
+}
+
+/// Guitar Pro files use the Windows encoding of their time, unless they are newer than that
+fn decode(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_owned(),
+        Err(_) => bytes.iter().map(|&byte| byte as char).collect(),
+    }
+}
+
+#[derive(Default)]
+struct Measure {
+    marker: Option<String>,
+    /// Key signature as the number of sharps, or flats when negative, and whether it is minor
+    key: Option<(i8, bool)>,
+}
+
+#[derive(Default)]
+struct Beat {
+    start: u32,
+    chord: Option<String>,
+    /// Whether the beat has notes that aren't tied to the previous beat, and so gets a syllable
+    /// of the lyrics
+    struck: bool,
+    /// Fret played on every string, from high to low
+    frets: Vec<Option<String>>,
+    duration: u32,
+}
+
+#[derive(Default)]
+struct Track {
+    name: String,
+    drums: bool,
+    /// Midi notes of the open strings, from high to low
+    tuning: Vec<u8>,
+    measures: Vec<Vec<Beat>>,
+}
+
+#[derive(Default)]
+struct GuitarPro {
+    title: String,
+    artist: String,
+    tempo: i32,
+    /// The lyrics track and the lyrics with the measure they start in
+    lyrics: (usize, Vec<(usize, String)>),
+    measures: Vec<Measure>,
+    tracks: Vec<Track>,
+}
+
+/// Converts a Guitar Pro 3, 4 or 5 file into ChordPro. Lyrics are written with the chords of
+/// the song, and the tracks are written as tab.
+pub fn import(data: &[u8]) -> Result<String> {
+    let song = read(data)?;
+    let mut chordpro = vec![format!("{{title: {}}}", song.title)];
+    if !song.artist.is_empty() {
+        chordpro.push(format!("{{subtitle: {}}}", song.artist));
+    }
+    // C major is what Guitar Pro uses when no key was set
+    if let Some((fifths, minor)) = song.measures.first().and_then(|measure| measure.key) {
+        if fifths != 0 || minor {
+            chordpro.push(format!(
+                "{{key: {}}}",
+                Key::from_fifths(fifths as i32, minor)
+            ));
+        }
+    }
+    if song.tempo > 0 {
+        chordpro.push(format!("{{tempo: {}}}", song.tempo));
+    }
+    chordpro.push(String::new());
+    chordpro.append(&mut lyrics(&song));
+    for track in song.tracks.iter().filter(|track| !track.drums) {
+        let tab = tab(track);
+        if !tab.is_empty() {
+            chordpro.push(String::new());
+            chordpro.push(format!("{{c: {}}}", track.name));
+            chordpro.push(String::from("{sot}"));
+            chordpro.extend(tab);
+            chordpro.push(String::from("{eot}"));
+        }
+    }
+    Ok(chordpro.join("\n") + "\n")
+}
+
+fn read(data: &[u8]) -> Result<GuitarPro> {
+    let mut reader = Reader {
+        data,
+        position: 0,
+        version: 0,
+    };
+    let version = reader.byte_size_string(30)?;
+    if !version.starts_with("FICHIER GUITAR PRO") {
+        return Err(Box::from("Not a Guitar Pro file"));
+    }
+    let number: String = version
+        .chars()
+        .rev()
+        .take(4)
+        .filter(char::is_ascii_digit)
+        .collect::<Vec<char>>()
+        .into_iter()
+        .rev()
+        .collect();
+    reader.version = number.parse().map_err(|_| "Unknown Guitar Pro version")?;
+    if !(300..600).contains(&reader.version) {
+        return Err(Box::from(format!(
+            "Guitar Pro version {} is not supported",
+            version
+        )));
+    }
+    let gp4 = reader.version >= 400;
+    let gp5 = reader.version >= 500;
+
+    let mut song = GuitarPro {
+        title: reader.int_byte_size_string()?,
+        ..Default::default()
+    };
+    reader.int_byte_size_string()?; // Subtitle
+    song.artist = reader.int_byte_size_string()?;
+    let fields = match gp5 {
+        // Album, words, music, copyright, tab and instructions
+        true => 6,
+        // Album, words, copyright, tab and instructions
+        false => 5,
+    };
+    for _ in 0..fields {
+        reader.int_byte_size_string()?;
+    }
+    for _ in 0..reader.i32()? {
+        reader.int_byte_size_string()?; // Notice
+    }
+
+    if !gp5 {
+        reader.bool()?; // Triplet feel
+    }
+    if gp4 {
+        let track = reader.i32()?;
+        let mut lyrics = vec![];
+        for _ in 0..5 {
+            let start = reader.i32()?;
+            let text = reader.int_size_string()?;
+            if !text.trim().is_empty() {
+                lyrics.push((start.saturating_sub(1).max(0) as usize, text));
+            }
+        }
+        song.lyrics = (track.saturating_sub(1).max(0) as usize, lyrics);
+    }
+    if reader.version >= 510 {
+        reader.skip(19)?; // Master volume, effect and equalizer
+    }
+    if gp5 {
+        // Page size, margins, proportion and header and footer flags
+        reader.skip(30)?;
+        for _ in 0..10 {
+            reader.int_byte_size_string()?; // Header and footer formats
+        }
+        reader.int_byte_size_string()?; // Tempo name
+    }
+    song.tempo = reader.i32()?;
+    if reader.version >= 510 {
+        reader.bool()?; // Hide tempo
+    }
+    reader.i32()?; // Key
+    if gp4 {
+        reader.i8()?; // Octave
+    }
+    reader.skip(64 * 12)?; // Midi channels
+    if gp5 {
+        reader.skip(19 * 2)?; // Directions
+        reader.i32()?; // Master reverb
+    }
+
+    let measure_count = reader.i32()?.max(0) as usize;
+    let track_count = reader.i32()?.max(0) as usize;
+    for number in 0..measure_count {
+        song.measures
+            .push(read_measure_header(&mut reader, number)?);
+    }
+    for number in 0..track_count {
+        song.tracks.push(read_track(&mut reader, number)?);
+    }
+    if gp5 {
+        reader.skip(if reader.version == 500 { 2 } else { 1 })?;
+    }
+    for _ in 0..measure_count {
+        for track in song.tracks.iter_mut() {
+            let strings = track.tuning.len();
+            let voices = if gp5 { 2 } else { 1 };
+            let mut beats = vec![];
+            for voice in 0..voices {
+                let mut start = 0;
+                for _ in 0..reader.i32()? {
+                    let beat = read_beat(&mut reader, strings, start)?;
+                    start += beat.duration;
+                    // Only the first voice is shown
+                    if voice == 0 {
+                        beats.push(beat);
+                    }
+                }
+            }
+            if gp5 {
+                reader.u8()?; // Line break
+            }
+            track.measures.push(beats);
+        }
+    }
+    Ok(song)
+}
+
+fn read_measure_header(reader: &mut Reader, number: usize) -> Result<Measure> {
+    let gp5 = reader.version >= 500;
+    if gp5 && number > 0 {
+        reader.skip(1)?;
+    }
+    let mut measure = Measure::default();
+    let flags = reader.u8()?;
+    if flags & 0x01 != 0 {
+        reader.u8()?; // Time signature numerator
+    }
+    if flags & 0x02 != 0 {
+        reader.u8()?; // Time signature denominator
+    }
+    if flags & 0x08 != 0 {
+        reader.u8()?; // Repeat count
+    }
+    // Guitar Pro 5 moved alternate endings after the marker and key signature
+    if flags & 0x10 != 0 && !gp5 {
+        reader.u8()?; // Alternate ending
+    }
+    if flags & 0x20 != 0 {
+        measure.marker = Some(reader.int_byte_size_string()?);
+        reader.skip(4)?; // Color
+    }
+    if flags & 0x40 != 0 {
+        let fifths = reader.i8()?;
+        let minor = reader.u8()? == 1;
+        measure.key = Some((fifths, minor));
+    }
+    if gp5 {
+        if flags & 0x10 != 0 {
+            reader.u8()?; // Alternate ending
+        }
+        if flags & 0x03 != 0 {
+            reader.skip(4)?; // Beams
+        }
+        if flags & 0x10 == 0 {
+            reader.skip(1)?;
+        }
+        reader.u8()?; // Triplet feel
+    }
+    Ok(measure)
+}
+
+fn read_track(reader: &mut Reader, number: usize) -> Result<Track> {
+    if reader.version >= 500 && (number == 0 || reader.version == 500) {
+        reader.skip(1)?;
+    }
+    let flags = reader.u8()?;
+    let mut track = Track {
+        drums: flags & 0x01 != 0,
+        name: reader.byte_size_string(40)?,
+        ..Default::default()
+    };
+    let strings = reader.i32()?.clamp(0, 7) as usize;
+    for i in 0..7 {
+        let tuning = reader.i32()?;
+        if i < strings {
+            track.tuning.push(tuning.clamp(0, 127) as u8);
+        }
+    }
+    // Port, channel, effect channel, fret count, capo and color
+    reader.skip(6 * 4)?;
+    if reader.version >= 500 {
+        // Flags, auto accentuation, bank, humanize, unknown values and RSE instrument
+        reader.skip(2 + 1 + 1 + 1 + 12 + 12 + 12)?;
+        match reader.version {
+            500 => reader.skip(3)?,
+            _ => {
+                reader.skip(4)?;
+                reader.skip(4)?; // Equalizer
+                reader.int_byte_size_string()?; // RSE effect
+                reader.int_byte_size_string()?; // RSE effect category
+            }
+        }
+    }
+    Ok(track)
+}
+
+fn read_beat(reader: &mut Reader, strings: usize, start: u32) -> Result<Beat> {
+    let gp4 = reader.version >= 400;
+    let gp5 = reader.version >= 500;
+    let flags = reader.u8()?;
+    let mut beat = Beat {
+        start,
+        frets: vec![None; strings],
+        ..Default::default()
+    };
+    let mut rest = false;
+    if flags & 0x40 != 0 {
+        // Empty beats and rests
+        rest = reader.u8()? != 1;
+    }
+
+    let value = 1u32 << (i32::from(reader.i8()?) + 2).clamp(0, 8);
+    let mut duration = QUARTER * 4 / value;
+    if flags & 0x01 != 0 {
+        duration += duration / 2;
+    }
+    if flags & 0x20 != 0 {
+        let tuplet = reader.i32()?.max(1) as u32;
+        let times = match tuplet {
+            3 => 2,
+            5..=7 => 4,
+            9..=13 => 8,
+            _ => tuplet,
+        };
+        duration = (u64::from(duration) * u64::from(times) / u64::from(tuplet)) as u32;
+    }
+    beat.duration = duration;
+
+    if flags & 0x02 != 0 {
+        beat.chord = read_chord(reader)?.filter(|name| !name.trim().is_empty());
+    }
+    if flags & 0x04 != 0 {
+        reader.int_byte_size_string()?; // Text
+    }
+    if flags & 0x08 != 0 {
+        read_beat_effects(reader)?;
+    }
+    if flags & 0x10 != 0 {
+        read_mix_table_change(reader)?;
+    }
+
+    let string_flags = reader.u8()?;
+    for string in 0..strings.min(7) {
+        if string_flags & (1 << (6 - string)) != 0 {
+            let (fret, tied) = read_note(reader)?;
+            if !rest {
+                beat.struck |= !tied;
+                beat.frets[string] = Some(fret);
+            }
+        }
+    }
+    if gp5 {
+        let flags = reader.i16()?;
+        if flags & 0x0800 != 0 {
+            reader.u8()?; // Secondary beam breaks
+        }
+    } else if !gp4 && beat.frets.iter().all(Option::is_none) {
+        beat.struck = false;
+    }
+    Ok(beat)
+}
+
+/// Reads a chord diagram, returning the name of the chord
+fn read_chord(reader: &mut Reader) -> Result<Option<String>> {
+    if reader.version >= 500 {
+        reader.skip(17)?;
+        let name = reader.byte_size_string(21)?;
+        reader.skip(4)?;
+        reader.skip(4 + 7 * 4)?; // First fret and frets
+        reader.skip(32)?;
+        return Ok(Some(name));
+    }
+    if reader.bool()? {
+        if reader.version >= 400 {
+            reader.skip(16)?;
+            let name = reader.byte_size_string(21)?;
+            reader.skip(4)?;
+            reader.skip(4 + 7 * 4)?;
+            reader.skip(32)?;
+            Ok(Some(name))
+        } else {
+            reader.skip(25)?;
+            let name = reader.byte_size_string(34)?;
+            reader.skip(4 + 6 * 4)?;
+            reader.skip(36)?;
+            Ok(Some(name))
+        }
+    } else {
+        let name = reader.int_byte_size_string()?;
+        if reader.i32()? > 0 {
+            reader.skip(6 * 4)?; // Frets
+        }
+        Ok(Some(name))
+    }
+}
+
+fn read_beat_effects(reader: &mut Reader) -> Result<()> {
+    if reader.version < 400 {
+        let flags = reader.u8()?;
+        if flags & 0x20 != 0 {
+            reader.u8()?; // Slap effect
+            reader.i32()?; // Tremolo bar
+        }
+        if flags & 0x40 != 0 {
+            reader.skip(2)?; // Stroke
+        }
+        return Ok(());
+    }
+    let flags1 = reader.u8()?;
+    let flags2 = reader.u8()?;
+    if flags1 & 0x20 != 0 {
+        reader.u8()?; // Slap effect
+    }
+    if flags2 & 0x04 != 0 {
+        read_bend(reader)?; // Tremolo bar
+    }
+    if flags1 & 0x40 != 0 {
+        reader.skip(2)?; // Stroke
+    }
+    if flags2 & 0x02 != 0 {
+        reader.u8()?; // Pick stroke
+    }
+    Ok(())
+}
+
+fn read_bend(reader: &mut Reader) -> Result<()> {
+    reader.skip(1 + 4)?; // Type and value
+    let points = reader.i32()?.max(0) as usize;
+    reader.skip(points * 9)
+}
+
+fn read_mix_table_change(reader: &mut Reader) -> Result<()> {
+    let gp5 = reader.version >= 500;
+    reader.i8()?; // Instrument
+    if gp5 {
+        reader.skip(16)?; // RSE instrument
+    }
+    let mut values = vec![];
+    for _ in 0..6 {
+        values.push(reader.i8()?); // Volume, balance, chorus, reverb, phaser and tremolo
+    }
+    if gp5 {
+        reader.int_byte_size_string()?; // Tempo name
+    }
+    let tempo = reader.i32()?;
+    for value in values {
+        if value >= 0 {
+            reader.u8()?; // Transition duration
+        }
+    }
+    if tempo >= 0 {
+        reader.u8()?;
+        if reader.version >= 510 {
+            reader.bool()?; // Hide tempo
+        }
+    }
+    if reader.version >= 400 {
+        reader.u8()?; // Which tracks the changes apply to
+    }
+    if gp5 {
+        reader.i8()?; // Wah effect
+    }
+    if reader.version >= 510 {
+        reader.int_byte_size_string()?; // RSE effect
+        reader.int_byte_size_string()?; // RSE effect category
+    }
+    Ok(())
+}
+
+/// Reads a note, returning its fret and whether it is tied to the previous note
+fn read_note(reader: &mut Reader) -> Result<(String, bool)> {
+    let gp5 = reader.version >= 500;
+    let flags = reader.u8()?;
+    let mut kind = 1;
+    if flags & 0x20 != 0 {
+        kind = reader.u8()?;
+    }
+    if flags & 0x01 != 0 && !gp5 {
+        reader.skip(2)?; // Duration and tuplet
+    }
+    if flags & 0x10 != 0 {
+        reader.i8()?; // Velocity
+    }
+    let mut fret = 0;
+    if flags & 0x20 != 0 {
+        fret = reader.i8()?;
+    }
+    if flags & 0x80 != 0 {
+        reader.skip(2)?; // Fingering
+    }
+    if gp5 {
+        if flags & 0x01 != 0 {
+            reader.skip(8)?; // Duration percent
+        }
+        reader.u8()?;
+    }
+    if flags & 0x08 != 0 {
+        read_note_effects(reader)?;
+    }
+    let fret = match kind {
+        // Dead notes
+        3 => String::from("x"),
+        _ => fret.to_string(),
+    };
+    Ok((fret, kind == 2))
+}
+
+fn read_note_effects(reader: &mut Reader) -> Result<()> {
+    if reader.version < 400 {
+        let flags = reader.u8()?;
+        if flags & 0x01 != 0 {
+            read_bend(reader)?;
+        }
+        if flags & 0x10 != 0 {
+            reader.skip(4)?; // Grace note
+        }
+        return Ok(());
+    }
+    let flags1 = reader.u8()?;
+    let flags2 = reader.u8()?;
+    if flags1 & 0x01 != 0 {
+        read_bend(reader)?;
+    }
+    if flags1 & 0x10 != 0 {
+        // Grace note
+        reader.skip(if reader.version >= 500 { 5 } else { 4 })?;
+    }
+    if flags2 & 0x04 != 0 {
+        reader.u8()?; // Tremolo picking
+    }
+    if flags2 & 0x08 != 0 {
+        reader.u8()?; // Slide
+    }
+    if flags2 & 0x10 != 0 {
+        // Harmonic
+        let harmonic = reader.u8()?;
+        if reader.version >= 500 {
+            match harmonic {
+                2 => reader.skip(3)?,
+                3 => reader.skip(1)?,
+                _ => (),
+            }
+        }
+    }
+    if flags2 & 0x20 != 0 {
+        reader.skip(2)?; // Trill
+    }
+    Ok(())
+}
+
+/// Writes the lyrics of a song with its chords. Without lyrics, the chords are written per
+/// measure.
+fn lyrics(song: &GuitarPro) -> Vec<String> {
+    // Chords can be in any track, the first one found at a time is used
+    let mut chords: Vec<(usize, u32, &str)> = vec![];
+    for track in song.tracks.iter() {
+        for (measure, beats) in track.measures.iter().enumerate() {
+            for beat in beats {
+                if let Some(chord) = &beat.chord {
+                    if !chords
+                        .iter()
+                        .any(|(m, start, _)| *m == measure && *start == beat.start)
+                    {
+                        chords.push((measure, beat.start, chord.trim()));
+                    }
+                }
+            }
+        }
+    }
+    chords.sort_by_key(|(measure, start, _)| (*measure, *start));
+
+    let mut lines = vec![];
+    let mut line = String::new();
+    let mut chords = chords.into_iter().peekable();
+    let (track, blocks) = &song.lyrics;
+    let track = song.tracks.get(*track);
+
+    match (blocks.first(), track) {
+        (Some((first, text)), Some(track)) => {
+            let mut syllables = syllables(text).into_iter();
+            'measures: for (number, beats) in track.measures.iter().enumerate().skip(*first) {
+                if let Some(marker) = &song.measures[number].marker {
+                    if !line.trim().is_empty() {
+                        lines.push(std::mem::take(&mut line).trim_end().to_owned());
+                    }
+                    if !lines.is_empty() {
+                        lines.push(String::new());
+                    }
+                    lines.push(format!("{{c: {}}}", marker));
+                }
+                for beat in beats.iter().filter(|beat| beat.struck) {
+                    while let Some((_, _, chord)) =
+                        chords.next_if(|(m, start, _)| (*m, *start) <= (number, beat.start))
+                    {
+                        line += &format!("[{}]", chord);
+                    }
+                    match syllables.next() {
+                        Some((syllable, separator)) => {
+                            line += &syllable;
+                            match separator {
+                                '\n' => lines.push(std::mem::take(&mut line).trim_end().to_owned()),
+                                '-' => (),
+                                _ => line.push(' '),
+                            }
+                        }
+                        None => break 'measures,
+                    }
+                }
+            }
+            if !line.trim().is_empty() {
+                lines.push(std::mem::take(&mut line).trim_end().to_owned());
+            }
+            // Other lyrics lines are usually other verses, which can't be lined up with chords
+            for (_, text) in blocks.iter().skip(1) {
+                lines.push(String::new());
+                lines.extend(text.lines().map(|line| line.trim().to_owned()));
+            }
+        }
+        _ => {
+            let mut measures = 0;
+            for (number, measure) in song.measures.iter().enumerate() {
+                if let Some(marker) = &measure.marker {
+                    if !line.is_empty() {
+                        lines.push(std::mem::take(&mut line).trim_end().to_owned());
+                    }
+                    if !lines.is_empty() {
+                        lines.push(String::new());
+                    }
+                    lines.push(format!("{{c: {}}}", marker));
+                    measures = 0;
+                }
+                let mut found = false;
+                while let Some((_, _, chord)) = chords.next_if(|(m, _, _)| *m == number) {
+                    line += &format!("[{}] ", chord);
+                    found = true;
+                }
+                if found {
+                    measures += 1;
+                    if measures % 4 == 0 {
+                        lines.push(std::mem::take(&mut line).trim_end().to_owned());
+                    }
+                }
+            }
+        }
+    }
+    if !line.trim().is_empty() {
+        lines.push(line.trim_end().to_owned());
+    }
+    lines
+}
+
+/// Splits Guitar Pro lyrics into syllables, along with what separates them from the next one.
+/// Text between brackets is left out.
+fn syllables(text: &str) -> Vec<(String, char)> {
+    let mut syllables = vec![];
+    let mut syllable = String::new();
+    let mut brackets = false;
+    for c in text.chars() {
+        match c {
+            '[' => brackets = true,
+            ']' => brackets = false,
+            _ if brackets => (),
+            ' ' | '\n' | '-' => {
+                if !syllable.is_empty() {
+                    syllables.push((std::mem::take(&mut syllable), c));
+                } else if c == '\n' {
+                    if let Some((_, separator)) = syllables.last_mut() {
+                        *separator = '\n';
+                    }
+                }
+            }
+            '\r' => (),
+            c => syllable.push(c),
+        }
+    }
+    if !syllable.is_empty() {
+        syllables.push((syllable, ' '));
+    }
+    syllables
+}
+
+/// Writes a track as tab, with a line for every string
+fn tab(track: &Track) -> Vec<String> {
+    if track.tuning.is_empty()
+        || track
+            .measures
+            .iter()
+            .flatten()
+            .all(|beat| beat.frets.iter().all(Option::is_none))
+    {
+        return vec![];
+    }
+
+    // Every measure is written as a column of strings, then as many measures as fit are put
+    // next to each other
+    let measures: Vec<Vec<String>> = track
+        .measures
+        .iter()
+        .map(|beats| {
+            let mut strings = vec![String::new(); track.tuning.len()];
+            for beat in beats {
+                let width = beat
+                    .frets
+                    .iter()
+                    .map(|fret| fret.as_ref().map_or(0, String::len))
+                    .max()
+                    .unwrap_or(0)
+                    + match beat.duration {
+                        d if d >= QUARTER * 2 => 4,
+                        d if d >= QUARTER => 2,
+                        _ => 1,
+                    };
+                for (string, fret) in strings.iter_mut().zip(beat.frets.iter()) {
+                    let fret = fret.as_deref().unwrap_or("");
+                    *string += "-";
+                    *string += fret;
+                    *string += &"-".repeat(width - fret.len());
+                }
+            }
+            strings.iter_mut().for_each(|string| string.push('|'));
+            strings
+        })
+        .collect();
+
+    let names: Vec<String> = track
+        .tuning
+        .iter()
+        .map(|note| Key::new(PitchClass::C, Mode::Major).spell(PitchClass::from_u8(note % 12)))
+        .collect();
+    let name_width = names.iter().map(String::len).max().unwrap_or(1);
+    let mut lines = vec![];
+    let mut system: Vec<String> = names
+        .iter()
+        .map(|name| format!("{:<width$}|", name, width = name_width))
+        .collect();
+    for measure in measures {
+        if system[0].len() + measure[0].len() > TAB_WIDTH && system[0].len() > name_width + 1 {
+            lines.append(&mut system);
+            lines.push(String::new());
+            system = names
+                .iter()
+                .map(|name| format!("{:<width$}|", name, width = name_width))
+                .collect();
+        }
+        for (line, string) in system.iter_mut().zip(measure.iter()) {
+            *line += string;
+        }
+    }
+    lines.append(&mut system);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GP3: &[u8] = include_bytes!("../../tests/fixtures/song.gp3");
+    const GP4: &[u8] = include_bytes!("../../tests/fixtures/song.gp4");
+    const GP5: &[u8] = include_bytes!("../../tests/fixtures/song.gp5");
+    const GP510: &[u8] = include_bytes!("../../tests/fixtures/song-5.10.gp5");
+
+    #[test]
+    fn imports_lyrics_chords_and_markers() {
+        for file in [GP4, GP5, GP510] {
+            let chordpro = import(file).unwrap();
+            assert!(chordpro.starts_with("{title: Test Song}\n{subtitle: The Band}\n{key: G}\n"));
+            assert!(
+                chordpro.contains("{c: Verse}\n[G]Hello [C]world\nsing [D]along [Em]\n"),
+                "{}",
+                chordpro
+            );
+        }
+        // Guitar Pro 3 has no lyrics, so only the chords are kept
+        let chordpro = import(GP3).unwrap();
+        assert!(
+            chordpro.contains("{c: Verse}\n[G] [C] [D] [Em]\n"),
+            "{}",
+            chordpro
+        );
+    }
+
+    #[test]
+    fn imports_tab() {
+        let tab = "{c: Guitar}\n{sot}\n\
+                   E|-3--------------|-2----------------------|\n\
+                   B|-----0---1------|-----3------------------|\n\
+                   G|-------------x--|---------0--------------|\n\
+                   D|----------------|-------------12---10--9-|\n\
+                   A|---------3------|------------------------|\n\
+                   E|-3--------------|------------------------|\n{eot}\n";
+        let chordpro = import(GP5).unwrap();
+        assert!(chordpro.ends_with(tab), "{}", chordpro);
+        // Drum tracks have no tab
+        assert!(!chordpro.contains("Drums"));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(import(b"FICHIER GUITAR PRO v9.99").is_err());
+        assert!(import(b"").is_err());
+        assert!(import(&GP5[..100]).is_err());
+    }
+}
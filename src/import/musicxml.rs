@@ -0,0 +1,408 @@
+use crate::key::Key;
+use roxmltree::{Document, Node, ParsingOptions};
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    io::{Cursor, Read},
+};
+
+/// Ticks in a quarter note, used to line up parts with different divisions
+const QUARTER: u32 = 960;
+
+#[derive(Default)]
+struct Measure {
+    /// Rehearsal marks, which usually name the sections of a lead sheet
+    label: Option<String>,
+    /// Whether the measure starts a new system, which is used to break lines
+    new_line: bool,
+    chords: Vec<(u32, String)>,
+    /// Syllables with their verse number and time, and whether they end a word
+    syllables: Vec<(String, u32, String, bool)>,
+}
+
+/// Converts a MusicXML score into ChordPro. Chord symbols are lined up with the lyrics of the
+/// score, with every verse of the lyrics written out. Compressed `.mxl` files are unpacked
+/// first.
+pub fn import(contents: &[u8]) -> Result<String, Box<dyn Error>> {
+    let input = match contents.starts_with(b"PK") {
+        true => unpack(contents)?,
+        false => String::from_utf8_lossy(contents).into_owned(),
+    };
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let document = Document::parse_with_options(&input, options)?;
+    let score = document.root_element();
+    match score.tag_name().name() {
+        "score-partwise" => (),
+        "score-timewise" => return Err(Box::from("Timewise MusicXML scores are not supported")),
+        _ => return Err(Box::from("Not a MusicXML score")),
+    }
+
+    let mut chordpro = vec![];
+    let title = child(score, "work")
+        .and_then(|work| child(work, "work-title"))
+        .or_else(|| child(score, "movement-title"))
+        .map(text)
+        .unwrap_or_default();
+    chordpro.push(format!("{{title: {}}}", title));
+    if let Some(identification) = child(score, "identification") {
+        let creators: Vec<String> = children(identification, "creator")
+            .filter(|creator| {
+                matches!(
+                    creator.attribute("type"),
+                    Some("composer" | "lyricist") | None
+                )
+            })
+            .map(text)
+            .filter(|creator| !creator.is_empty())
+            .collect();
+        if !creators.is_empty() {
+            chordpro.push(format!("{{subtitle: {}}}", creators.join(", ")));
+        }
+    }
+    let key = score.descendants().find(|node| node.has_tag_name("key"));
+    if let Some(fifths) = key
+        .and_then(|key| child(key, "fifths"))
+        .and_then(|fifths| text(fifths).parse::<i32>().ok())
+    {
+        let minor = key
+            .and_then(|key| child(key, "mode"))
+            .is_some_and(|mode| text(mode) == "minor");
+        chordpro.push(format!("{{key: {}}}", Key::from_fifths(fifths, minor)));
+    }
+    let tempo = score
+        .descendants()
+        .find_map(|node| match node.tag_name().name() {
+            "sound" => node.attribute("tempo").map(str::to_owned),
+            "per-minute" => Some(text(node)),
+            _ => None,
+        })
+        .and_then(|tempo| tempo.parse::<f64>().ok());
+    if let Some(tempo) = tempo {
+        chordpro.push(format!("{{tempo: {}}}", tempo.round()));
+    }
+
+    let measures = measures(score);
+    let verses: BTreeSet<&str> = measures
+        .iter()
+        .flat_map(|measure| measure.syllables.iter())
+        .map(|(verse, ..)| verse.as_str())
+        .collect();
+    if verses.is_empty() {
+        chordpro.push(String::new());
+        chordpro.append(&mut lines(&measures, None, true));
+    }
+    for (index, verse) in verses.iter().enumerate() {
+        chordpro.push(String::new());
+        if verses.len() > 1 {
+            chordpro.push(format!("{{c: Verse {}}}", verse));
+        }
+        chordpro.append(&mut lines(&measures, Some(verse), index == 0));
+    }
+    Ok(chordpro.join("\n") + "\n")
+}
+
+/// Finds the score in a compressed MusicXML file through its container
+fn unpack(contents: &[u8]) -> Result<String, Box<dyn Error>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(contents))?;
+    let mut read = |name: &str| -> Result<String, Box<dyn Error>> {
+        let mut file = String::new();
+        archive.by_name(name)?.read_to_string(&mut file)?;
+        Ok(file)
+    };
+    let container = read("META-INF/container.xml")?;
+    let container = Document::parse(&container)?;
+    let path = container
+        .descendants()
+        .find(|node| node.has_tag_name("rootfile"))
+        .and_then(|rootfile| rootfile.attribute("full-path"))
+        .ok_or("Compressed MusicXML file without a score")?;
+    read(path)
+}
+
+/// Reads the chords, lyrics, labels and line breaks of every measure. Chords are taken from
+/// every part, while the lyrics come from the first part that has them.
+fn measures(score: Node) -> Vec<Measure> {
+    let mut measures: Vec<Measure> = vec![];
+    let parts: Vec<Node> = children(score, "part").collect();
+    let lyrics = parts
+        .iter()
+        .position(|part| part.descendants().any(|node| node.has_tag_name("lyric")))
+        .unwrap_or(0);
+    for (number, part) in parts.iter().enumerate() {
+        let mut divisions = 1;
+        for (index, xml) in children(*part, "measure").enumerate() {
+            if measures.len() <= index {
+                measures.push(Measure::default());
+            }
+            let measure = &mut measures[index];
+            let mut time: u32 = 0;
+            let mut last = 0;
+            for node in xml.children().filter(Node::is_element) {
+                let duration = child(node, "duration")
+                    .and_then(|duration| text(duration).parse::<u64>().ok())
+                    .map_or(0, |duration| {
+                        duration.saturating_mul(QUARTER.into()) / u64::from(divisions)
+                    });
+                let duration = duration.min(u32::MAX.into()) as u32;
+                match node.tag_name().name() {
+                    "attributes" => {
+                        if let Some(value) = child(node, "divisions")
+                            .and_then(|divisions| text(divisions).parse::<u32>().ok())
+                        {
+                            divisions = value.max(1);
+                        }
+                    }
+                    "print" => {
+                        measure.new_line |= node.attribute("new-system") == Some("yes")
+                            || node.attribute("new-page") == Some("yes");
+                    }
+                    "direction" => {
+                        if let Some(rehearsal) = node
+                            .descendants()
+                            .find(|node| node.has_tag_name("rehearsal"))
+                        {
+                            measure.label.get_or_insert_with(|| text(rehearsal));
+                        }
+                    }
+                    "harmony" => {
+                        let offset = child(node, "offset")
+                            .and_then(|offset| text(offset).parse::<i32>().ok())
+                            .map_or(0, |offset| {
+                                i64::from(offset) * i64::from(QUARTER) / i64::from(divisions)
+                            });
+                        let time = (i64::from(time) + offset).clamp(0, u32::MAX.into()) as u32;
+                        if let Some(chord) = chord(node) {
+                            if !measure.chords.iter().any(|(start, _)| *start == time) {
+                                measure.chords.push((time, chord));
+                            }
+                        }
+                    }
+                    "note" => {
+                        // Notes of a chord start with the note before them
+                        let start = match child(node, "chord") {
+                            Some(_) => last,
+                            None => time,
+                        };
+                        if number == lyrics {
+                            for lyric in children(node, "lyric") {
+                                let verse = lyric.attribute("number").unwrap_or("1");
+                                let syllable: String = children(lyric, "text").map(text).collect();
+                                let end = matches!(
+                                    child(lyric, "syllabic").map(text).as_deref(),
+                                    None | Some("single" | "end")
+                                );
+                                if !syllable.is_empty() {
+                                    measure.syllables.push((
+                                        verse.to_owned(),
+                                        start,
+                                        syllable,
+                                        end,
+                                    ));
+                                }
+                            }
+                        }
+                        if child(node, "chord").is_none() {
+                            last = time;
+                            time = time.saturating_add(duration);
+                        }
+                    }
+                    "backup" => time = time.saturating_sub(duration),
+                    "forward" => time = time.saturating_add(duration),
+                    _ => (),
+                }
+            }
+        }
+    }
+    for measure in measures.iter_mut() {
+        measure.chords.sort_by_key(|(time, _)| *time);
+        measure.syllables.sort_by_key(|(_, time, ..)| *time);
+    }
+    measures
+}
+
+/// Writes one verse of the lyrics with the chords. Lines are broken where the score starts a
+/// new system, or every four measures when it has none. Lines without lyrics are only kept for
+/// the first verse.
+fn lines(measures: &[Measure], verse: Option<&str>, first: bool) -> Vec<String> {
+    let systems = measures.iter().any(|measure| measure.new_line);
+    let mut lines = vec![];
+    let mut line = String::new();
+    let mut lyrics = false;
+    let end = |line: &mut String, lyrics: &mut bool, lines: &mut Vec<String>| {
+        if !line.trim().is_empty() && (*lyrics || first) {
+            lines.push(line.trim_end().to_owned());
+        }
+        line.clear();
+        *lyrics = false;
+    };
+    for (index, measure) in measures.iter().enumerate() {
+        let new_line = match systems {
+            true => measure.new_line,
+            false => index % 4 == 0,
+        };
+        if new_line || measure.label.is_some() {
+            end(&mut line, &mut lyrics, &mut lines);
+        }
+        if let Some(label) = &measure.label {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(format!("{{c: {}}}", label));
+        }
+        let mut chords = measure.chords.iter().peekable();
+        let syllables = measure
+            .syllables
+            .iter()
+            .filter(|(number, ..)| Some(number.as_str()) == verse);
+        for (_, time, syllable, word_end) in syllables {
+            // Chords over rests are kept apart from the next syllable
+            while let Some((start, chord)) = chords.next_if(|(start, _)| start <= time) {
+                line += &format!("[{}]", chord);
+                if start < time {
+                    line.push(' ');
+                }
+            }
+            line += syllable;
+            if *word_end {
+                line.push(' ');
+            }
+            lyrics = true;
+        }
+        for (_, chord) in chords {
+            line += &format!("[{}] ", chord);
+        }
+    }
+    end(&mut line, &mut lyrics, &mut lines);
+    lines
+}
+
+/// Writes a chord symbol like `F#m7/C#` from its root, kind and bass note
+fn chord(harmony: Node) -> Option<String> {
+    let step = |node: Node, name: &str| -> Option<String> {
+        let step = text(child(node, &format!("{}-step", name))?);
+        let alter = child(node, &format!("{}-alter", name))
+            .and_then(|alter| text(alter).parse::<f64>().ok())
+            .unwrap_or(0.0);
+        Some(match alter {
+            alter if alter > 0.0 => step + "#",
+            alter if alter < 0.0 => step + "b",
+            _ => step,
+        })
+    };
+    let kind = child(harmony, "kind")?;
+    if text(kind) == "none" {
+        // No chord is an annotation, so it isn't transposed like a chord
+        return Some(String::from("*N.C."));
+    }
+    let root = step(child(harmony, "root")?, "root")?;
+    let suffix = match kind.attribute("text") {
+        Some(text) => text.to_owned(),
+        None => match text(kind).as_str() {
+            "minor" => "m",
+            "augmented" => "aug",
+            "diminished" => "dim",
+            "dominant" => "7",
+            "major-seventh" => "maj7",
+            "minor-seventh" => "m7",
+            "diminished-seventh" => "dim7",
+            "augmented-seventh" => "aug7",
+            "half-diminished" => "m7b5",
+            "major-minor" => "m(maj7)",
+            "major-sixth" => "6",
+            "minor-sixth" => "m6",
+            "dominant-ninth" => "9",
+            "major-ninth" => "maj9",
+            "minor-ninth" => "m9",
+            "dominant-11th" => "11",
+            "major-11th" => "maj11",
+            "minor-11th" => "m11",
+            "dominant-13th" => "13",
+            "major-13th" => "maj13",
+            "minor-13th" => "m13",
+            "suspended-second" => "sus2",
+            "suspended-fourth" => "sus4",
+            "power" => "5",
+            _ => "",
+        }
+        .to_owned(),
+    };
+    let degrees: String = children(harmony, "degree")
+        .filter_map(|degree| {
+            let value = text(child(degree, "degree-value")?);
+            let alter = child(degree, "degree-alter")
+                .and_then(|alter| text(alter).parse::<i32>().ok())
+                .unwrap_or(0);
+            let accidental = match alter {
+                alter if alter > 0 => "#",
+                alter if alter < 0 => "b",
+                _ => "",
+            };
+            match text(child(degree, "degree-type")?).as_str() {
+                "add" => Some(format!("add{}{}", accidental, value)),
+                "subtract" => Some(format!("no{}", value)),
+                _ => Some(format!("{}{}", accidental, value)),
+            }
+        })
+        .collect();
+    let bass = child(harmony, "bass")
+        .and_then(|bass| step(bass, "bass"))
+        .map(|bass| format!("/{}", bass))
+        .unwrap_or_default();
+    Some(format!("{}{}{}{}", root, suffix, degrees, bass))
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.tag_name().name() == name)
+}
+
+fn children<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |child| child.tag_name().name() == name)
+}
+
+fn text(node: Node) -> String {
+    node.descendants()
+        .filter(Node::is_text)
+        .filter_map(|node| node.text())
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEAD: &[u8] = include_bytes!("../../tests/fixtures/lead.musicxml");
+    const COMPRESSED: &[u8] = include_bytes!("../../tests/fixtures/lead.mxl");
+
+    #[test]
+    fn imports_lead_sheets() {
+        let chordpro = import(LEAD).unwrap();
+        assert!(chordpro.starts_with("{title: Lead Sheet}\n{subtitle: Jane Doe}\n{key: F}\n"));
+        assert!(
+            chordpro.contains("[F]Happy [Bb7/D]day\n[Csus4add9] [Fmaj7]to [*N.C.]you\n"),
+            "{}",
+            chordpro
+        );
+        assert!(chordpro.contains("{c: Verse 2}\n{c: Verse}\n[F]Some more [Bb7/D]words\n"));
+    }
+
+    #[test]
+    fn imports_compressed_scores() {
+        assert_eq!(import(COMPRESSED).unwrap(), import(LEAD).unwrap());
+    }
+
+    #[test]
+    fn rejects_other_documents() {
+        assert!(import(b"<song><title>Grace</title></song>").is_err());
+        assert!(import(b"PK\x03\x04").is_err());
+    }
+}
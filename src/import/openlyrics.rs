@@ -0,0 +1,214 @@
+use roxmltree::{Document, Node};
+use std::error::Error;
+
+/// Converts an OpenLyrics song into ChordPro. Verses are written in the verse order of the
+/// song when it has one, with choruses marked as such.
+pub fn import(input: &str) -> Result<String, Box<dyn Error>> {
+    let document = Document::parse(input)?;
+    let song = document.root_element();
+    if song.tag_name().name() != "song" {
+        return Err(Box::from("Not an OpenLyrics song"));
+    }
+
+    let mut chordpro = vec![];
+    let mut order = String::new();
+    if let Some(properties) = child(song, "properties") {
+        if let Some(title) = child(properties, "titles").and_then(|titles| child(titles, "title")) {
+            chordpro.push(format!("{{title: {}}}", text(title)));
+        }
+        if let Some(authors) = child(properties, "authors") {
+            let authors: Vec<String> = children(authors, "author").map(text).collect();
+            if !authors.is_empty() {
+                chordpro.push(format!("{{subtitle: {}}}", authors.join(", ")));
+            }
+        }
+        if let Some(key) = child(properties, "key") {
+            chordpro.push(format!("{{key: {}}}", text(key)));
+        }
+        if let Some(verse_order) = child(properties, "verseOrder") {
+            order = text(verse_order);
+        }
+    }
+
+    let verses: Vec<Node> = child(song, "lyrics")
+        .map(|lyrics| children(lyrics, "verse").collect())
+        .unwrap_or_default();
+    let order: Vec<Node> = order
+        .split_whitespace()
+        .filter_map(|name| {
+            verses
+                .iter()
+                .find(|verse| verse.attribute("name") == Some(name))
+                .copied()
+        })
+        .collect();
+    let verses = match order.is_empty() {
+        true => verses,
+        false => order,
+    };
+
+    for verse in verses {
+        let name = verse.attribute("name").unwrap_or("v");
+        chordpro.push(String::new());
+        chordpro.push(format!("{{c: {}}}", label(name)));
+        let chorus = name.starts_with('c');
+        if chorus {
+            chordpro.push(String::from("{soc}"));
+        }
+        for lines in children(verse, "lines") {
+            let mut line = String::new();
+            self::lines(lines, &mut line);
+            chordpro.extend(line.split('\n').map(|line| line.trim().to_owned()));
+        }
+        if chorus {
+            chordpro.push(String::from("{eoc}"));
+        }
+    }
+    Ok(chordpro.join("\n") + "\n")
+}
+
+/// Writes the contents of a `lines` element as ChordPro, with a newline for every `br`
+fn lines(node: Node, chordpro: &mut String) {
+    for child in node.children() {
+        if child.is_text() {
+            let text = child.text().unwrap_or_default();
+            // Whitespace is collapsed like in HTML, only `br` breaks lines
+            let mut collapsed: String = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+            if text.starts_with(char::is_whitespace) && !collapsed.is_empty() {
+                collapsed.insert(0, ' ');
+            }
+            if text.ends_with(char::is_whitespace) {
+                collapsed.push(' ');
+            }
+            *chordpro += &collapsed;
+            continue;
+        }
+        match child.tag_name().name() {
+            "br" => chordpro.push('\n'),
+            "chord" => {
+                *chordpro += &format!("[{}]", chord(child));
+                // Newer versions put the lyrics a chord belongs to inside the chord
+                lines(child, chordpro);
+            }
+            "comment" => *chordpro += &format!("{{c: {}}}", text(child)),
+            _ => lines(child, chordpro),
+        }
+    }
+}
+
+/// Reads a chord from its name, or from its root, structure and bass note
+fn chord(node: Node) -> String {
+    if let Some(name) = node.attribute("name") {
+        return name.to_owned();
+    }
+    let root = node.attribute("root").unwrap_or_default();
+    let structure = match node.attribute("structure").unwrap_or_default() {
+        "" | "maj" => "",
+        "min" => "m",
+        "dom7" => "7",
+        "maj7" => "maj7",
+        "min7" => "m7",
+        "dim" => "dim",
+        "aug" => "aug",
+        "sus2" => "sus2",
+        "sus4" => "sus4",
+        "dom9" => "9",
+        "add9" => "add9",
+        "maj6" => "6",
+        "min6" => "m6",
+        structure => structure,
+    };
+    match node.attribute("bass") {
+        Some(bass) => format!("{}{}/{}", root, structure, bass),
+        None => format!("{}{}", root, structure),
+    }
+}
+
+/// Turns verse names like `v1`, `c` and `b2` into labels like "Verse 1", "Chorus" and "Bridge 2"
+fn label(name: &str) -> String {
+    let mut chars = name.chars();
+    let kind = match chars.next() {
+        Some('v') => "Verse",
+        Some('c') => "Chorus",
+        Some('b') => "Bridge",
+        Some('p') => "Pre-Chorus",
+        Some('i') => "Intro",
+        Some('e') => "Ending",
+        _ => return name.to_owned(),
+    };
+    let number = chars.as_str();
+    match number.is_empty() {
+        true => kind.to_owned(),
+        false => format!("{} {}", kind, number),
+    }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &'a str) -> Option<Node<'a, 'input>> {
+    children(node, name).next()
+}
+
+fn children<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |child| child.tag_name().name() == name)
+}
+
+fn text(node: Node) -> String {
+    node.descendants()
+        .filter(Node::is_text)
+        .filter_map(|node| node.text())
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SONG: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<song xmlns="http://openlyrics.info/namespace/2009/song" version="0.9">
+  <properties>
+    <titles><title>Amazing Grace</title></titles>
+    <authors><author>John Newton</author><author>Traditional</author></authors>
+    <key>G</key>
+    <verseOrder>v1 c v1</verseOrder>
+  </properties>
+  <lyrics>
+    <verse name="c">
+      <lines><chord root="E" structure="min"/>I once was <chord name="D"/>lost</lines>
+    </verse>
+    <verse name="v1">
+      <lines>A<chord name="G">mazing</chord> grace<br/>
+        <comment>slowly</comment>how sweet</lines>
+    </verse>
+  </lyrics>
+</song>"#;
+
+    #[test]
+    fn imports_verses_in_order() {
+        assert_eq!(
+            import(SONG).unwrap(),
+            "{title: Amazing Grace}\n{subtitle: John Newton, Traditional}\n{key: G}\n\n\
+             {c: Verse 1}\nA[G]mazing grace\n{c: slowly}how sweet\n\n\
+             {c: Chorus}\n{soc}\n[Em]I once was [D]lost\n{eoc}\n\n\
+             {c: Verse 1}\nA[G]mazing grace\n{c: slowly}how sweet\n"
+        );
+    }
+
+    #[test]
+    fn rejects_other_documents() {
+        assert!(import("<html/>").is_err());
+        assert!(import("not xml").is_err());
+    }
+
+    #[test]
+    fn labels_verses() {
+        assert_eq!(label("v2"), "Verse 2");
+        assert_eq!(label("c"), "Chorus");
+        assert_eq!(label("p1"), "Pre-Chorus 1");
+        assert_eq!(label("x"), "x");
+    }
+}
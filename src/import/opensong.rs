@@ -0,0 +1,246 @@
+use super::text::merge;
+use roxmltree::Document;
+use std::error::Error;
+
+/// Converts an OpenSong song into ChordPro. Sections are written in the presentation order of
+/// the song when it has one.
+pub fn import(input: &str) -> Result<String, Box<dyn Error>> {
+    let document = Document::parse(input)?;
+    let song = document.root_element();
+    if song.tag_name().name() != "song" || song.tag_name().namespace().is_some() {
+        return Err(Box::from("Not an OpenSong song"));
+    }
+    let field = |name: &str| {
+        song.children()
+            .find(|child| child.tag_name().name() == name)
+            .and_then(|child| child.text())
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+    };
+    let lyrics = field("lyrics").ok_or("OpenSong song without lyrics")?;
+
+    let mut chordpro = vec![];
+    if let Some(title) = field("title") {
+        chordpro.push(format!("{{title: {}}}", title));
+    }
+    if let Some(author) = field("author") {
+        chordpro.push(format!("{{subtitle: {}}}", author));
+    }
+    if let Some(key) = field("key") {
+        chordpro.push(format!("{{key: {}}}", key));
+    }
+    if let Some(capo) = field("capo") {
+        chordpro.push(format!("{{capo: {}}}", capo));
+    }
+
+    let sections = sections(lyrics);
+    let order: Vec<&(String, Vec<String>)> = field("presentation")
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|id| sections.iter().find(|(section, _)| section == id))
+        .collect();
+    let sections = match order.is_empty() {
+        true => sections.iter().collect(),
+        false => order,
+    };
+
+    for (id, lines) in sections {
+        chordpro.push(String::new());
+        if !id.is_empty() {
+            chordpro.push(format!("{{c: {}}}", label(id)));
+        }
+        let chorus = label(id).starts_with("Chorus");
+        if chorus {
+            chordpro.push(String::from("{soc}"));
+        }
+        chordpro.extend(lines.iter().cloned());
+        if chorus {
+            chordpro.push(String::from("{eoc}"));
+        }
+    }
+    Ok(chordpro.join("\n") + "\n")
+}
+
+/// Splits lyrics into sections at markers like `[V1]`, converting the lines of each section to
+/// ChordPro. Lines numbered `1`, `2` and so on hold the lines of several verses below one chord
+/// line, and are split into sections like `V1` and `V2`.
+fn sections(lyrics: &str) -> Vec<(String, Vec<String>)> {
+    let mut sections = vec![(String::new(), vec![])];
+    // The section of the last marker, which numbered lines are split from
+    let mut current = 0;
+    let mut split: Vec<String> = vec![];
+    let mut chords: Option<&str> = None;
+    // Whether numbered lines use the chord line, which stays for the lines of the next verses
+    let mut shared = false;
+    for line in lyrics.lines() {
+        let (kind, rest) = line.split_at(line.chars().next().map_or(0, char::len_utf8));
+        let numbered = kind.starts_with(|c: char| c.is_ascii_digit());
+        // Chord lines without lyrics below them are kept on their own
+        if matches!(kind, "[" | "." | ";" | "-") || (shared && !numbered) {
+            if let Some(chords) = chords.take().filter(|_| !shared) {
+                sections[current].1.push(merge(chords, ""));
+            }
+            shared = false;
+        }
+        match kind {
+            "[" => {
+                let id = rest.trim_end().trim_end_matches(']').trim().to_owned();
+                sections.push((id, vec![]));
+                current = sections.len() - 1;
+            }
+            "." => chords = Some(rest),
+            ";" => sections[current].1.push(format!("{{c: {}}}", rest.trim())),
+            // Page and slide breaks only matter on a projector
+            "-" => (),
+            _ if numbered => {
+                let base = sections[current].0.clone();
+                let id = format!("{}{}", base, kind);
+                let index = match sections[current..]
+                    .iter()
+                    .position(|(other, _)| *other == id)
+                {
+                    Some(index) => current + index,
+                    None => {
+                        sections.push((id, vec![]));
+                        sections.len() - 1
+                    }
+                };
+                if !split.contains(&base) {
+                    split.push(base);
+                }
+                shared = chords.is_some();
+                sections[index].1.push(lyric_line(chords, rest));
+            }
+            _ => {
+                // Lyric lines start with a space, but not always
+                let lyrics = match kind {
+                    " " => rest,
+                    _ => line,
+                };
+                sections[current].1.push(lyric_line(chords.take(), lyrics));
+            }
+        }
+    }
+    if let Some(chords) = chords.filter(|_| !shared) {
+        sections[current].1.push(merge(chords, ""));
+    }
+    for (_, lines) in sections.iter_mut() {
+        while lines.last().is_some_and(String::is_empty) {
+            lines.pop();
+        }
+    }
+    sections.retain(|(id, lines)| !lines.is_empty() || !(id.is_empty() || split.contains(id)));
+    sections
+}
+
+/// Puts the chords of a chord line into the lyrics below it. Bars and underscores, which only
+/// space out the lyrics on a projector, are left out once the chords are in their columns.
+fn lyric_line(chords: Option<&str>, lyrics: &str) -> String {
+    match chords {
+        Some(chords) => merge(chords, lyrics).replace(['|', '_'], ""),
+        None => lyrics.replace(['|', '_'], "").trim_end().to_owned(),
+    }
+}
+
+/// Turns section markers like `V1`, `C` and `B2` into labels like "Verse 1", "Chorus" and
+/// "Bridge 2"
+fn label(id: &str) -> String {
+    let mut chars = id.chars();
+    let kind = match chars.next() {
+        Some('V') => "Verse",
+        Some('C') => "Chorus",
+        Some('B') => "Bridge",
+        Some('P') => "Pre-Chorus",
+        Some('T') => "Tag",
+        Some('I') => "Intro",
+        Some('E') => "Ending",
+        _ => return id.to_owned(),
+    };
+    let number = chars.as_str();
+    if !number.chars().all(|c| c.is_ascii_digit()) {
+        return id.to_owned();
+    }
+    match number.is_empty() {
+        true => kind.to_owned(),
+        false => format!("{} {}", kind, number),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SONG: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<song>
+  <title>Amazing Grace</title>
+  <author>John Newton</author>
+  <key>G</key>
+  <capo print=\"true\">2</capo>
+  <presentation>V1 C V1</presentation>
+  <lyrics>[C]
+.Em         D
+ I once was lost
+[V1]
+;Slowly
+.G       C
+ Amazing grace
+.G
+  </lyrics>
+</song>";
+
+    #[test]
+    fn imports_sections_in_presentation_order() {
+        assert_eq!(
+            import(SONG).unwrap(),
+            "{title: Amazing Grace}\n{subtitle: John Newton}\n{key: G}\n{capo: 2}\n\n\
+             {c: Verse 1}\n{c: Slowly}\n[G]Amazing [C]grace\n[G]\n\n\
+             {c: Chorus}\n{soc}\n[Em]I once was [D]lost\n{eoc}\n\n\
+             {c: Verse 1}\n{c: Slowly}\n[G]Amazing [C]grace\n[G]\n"
+        );
+    }
+
+    #[test]
+    fn rejects_other_documents() {
+        assert!(import("<song xmlns=\"http://openlyrics.info/namespace/2009/song\"/>").is_err());
+        assert!(import("<song><title>No lyrics</title></song>").is_err());
+    }
+
+    #[test]
+    fn labels_sections() {
+        assert_eq!(label("V2"), "Verse 2");
+        assert_eq!(label("C"), "Chorus");
+        assert_eq!(label("T"), "Tag");
+        assert_eq!(label("Coda"), "Coda");
+    }
+
+    #[test]
+    fn splits_numbered_lines_into_verses() {
+        let song = "<song><presentation>V2 C V1</presentation><lyrics>[V]
+.G       C
+1Amazing grace
+2Through many
+ 
+[C]
+ Praise</lyrics></song>";
+        assert_eq!(
+            import(song).unwrap(),
+            "\n{c: Verse 2}\n[G]Through [C]many\n\n\
+             {c: Chorus}\n{soc}\nPraise\n{eoc}\n\n\
+             {c: Verse 1}\n[G]Amazing [C]grace\n"
+        );
+        // Without a presentation order, the verses follow each other
+        let song = song.replace("<presentation>V2 C V1</presentation>", "");
+        assert!(import(&song).unwrap().starts_with(
+            "\n{c: Verse 1}\n[G]Amazing [C]grace\n\n{c: Verse 2}\n[G]Through [C]many\n"
+        ));
+    }
+
+    #[test]
+    fn keeps_chords_in_their_columns_around_bars() {
+        let song = "<song><lyrics>.G        C\n Amaz_ing gr_ace||\n Sweet | sound</lyrics></song>";
+        assert_eq!(
+            import(song).unwrap(),
+            "\n[G]Amazing [C]grace\nSweet  sound\n"
+        );
+    }
+}
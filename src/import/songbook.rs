@@ -0,0 +1,317 @@
+extern crate rust_music_theory as rustmt;
+
+use super::Backup;
+use crate::key::{Key, Mode};
+use crate::parser::Song;
+use rustmt::note::PitchClass;
+use serde_json::Value;
+use std::{
+    error::Error,
+    io::{Cursor, Read},
+    path::Path,
+};
+
+/// Extensions Linkesoft SongBook uses for ChordPro songs
+const CHORDPRO: [&str; 6] = ["chopro", "cho", "chordpro", "crd", "pro", "txt"];
+
+/// Reads a Linkesoft SongBook backup, a zip archive of ChordPro songs. Sets are read from `.set`
+/// files listing one song title per line, optionally followed by a key like `[G]`.
+pub fn linkesoft(contents: &[u8]) -> Result<Backup, Box<dyn Error>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(contents))?;
+    let mut songs = vec![];
+    let mut sets = vec![];
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        if file.is_dir() {
+            continue;
+        }
+        let path = Path::new(file.name()).to_owned();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+        let text = String::from_utf8_lossy(&bytes).into_owned();
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("set") => sets.push((stem.into_owned(), text)),
+            Some(extension) if CHORDPRO.contains(&extension.to_lowercase().as_str()) => {
+                // Songs without a title are named after their file
+                match Song::get_name(&text) {
+                    Some(_) => songs.push(text),
+                    None => songs.push(format!("{{title: {}}}\n{}", stem, text)),
+                }
+            }
+            _ => (),
+        }
+    }
+    if songs.is_empty() {
+        return Err(Box::from("No songs found in the SongBook backup"));
+    }
+
+    let mut library = Backup {
+        songs,
+        playlists: vec![],
+        missing: vec![],
+    };
+    let titles: Vec<String> = library
+        .songs
+        .iter()
+        .map(|song| Song::from(song.to_owned()).title)
+        .collect();
+    for (name, set) in sets {
+        let mut playlist = vec![name];
+        for line in set.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (title, key) = match line.rsplit_once(" [") {
+                Some((title, key)) if key.ends_with(']') => {
+                    (title, key.trim_end_matches(']').parse().ok())
+                }
+                _ => (line, None),
+            };
+            let song = titles
+                .iter()
+                .position(|song| song.eq_ignore_ascii_case(title))
+                .map(|index| &library.songs[index]);
+            match song {
+                Some(song) => playlist.push(entry(song, key, None)),
+                None => library
+                    .missing
+                    .push((playlist[0].clone(), title.to_owned())),
+            }
+        }
+        library.playlists.push(playlist.join("\n") + "\n");
+    }
+    Ok(library)
+}
+
+/// Reads a SongBook Pro `.sbp` backup, a zip archive with the songs and sets of the app as JSON.
+/// Set entries keep the key they are played in.
+pub fn songbook_pro(contents: &[u8]) -> Result<Backup, Box<dyn Error>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(contents))?;
+    let mut data = String::new();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        if !file.is_dir() && file.name().ends_with("dataFile.txt") {
+            file.read_to_string(&mut data)?;
+            break;
+        }
+    }
+    // The data starts with the version of the backup on a line of its own
+    let data = &data[data.find('{').ok_or("Not a SongBook Pro backup")?..];
+    let data: Value = serde_json::from_str(data)?;
+
+    let mut library = Backup {
+        songs: vec![],
+        playlists: vec![],
+        missing: vec![],
+    };
+    let mut ids = vec![];
+    for song in field(&data, "songs")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        if field(song, "deleted").and_then(Value::as_bool) == Some(true) {
+            continue;
+        }
+        let content = string(song, "content").unwrap_or_default();
+        let mut chordpro = vec![];
+        let has = |tag: &str| content.contains(&format!("{{{}:", tag));
+        if !has("title") && !has("t") {
+            chordpro.push(format!(
+                "{{title: {}}}",
+                string(song, "name").unwrap_or_default()
+            ));
+        }
+        if !has("subtitle") && !has("st") {
+            if let Some(author) = string(song, "author").filter(|author| !author.is_empty()) {
+                chordpro.push(format!("{{subtitle: {}}}", author));
+            }
+        }
+        if !has("key") {
+            if let Some(key) = key(song) {
+                chordpro.push(format!("{{key: {}}}", key));
+            }
+        }
+        if !has("capo") {
+            if let Some(capo) = number(song, "capo").filter(|capo| *capo > 0) {
+                chordpro.push(format!("{{capo: {}}}", capo));
+            }
+        }
+        if !has("tempo") {
+            if let Some(tempo) = number(song, "tempo").filter(|tempo| *tempo > 0) {
+                chordpro.push(format!("{{tempo: {}}}", tempo));
+            }
+        }
+        chordpro.push(content);
+        ids.push((number(song, "id"), library.songs.len()));
+        library
+            .songs
+            .push(chordpro.join("\n").trim_end().to_owned() + "\n");
+    }
+    if library.songs.is_empty() {
+        return Err(Box::from("No songs found in the SongBook Pro backup"));
+    }
+
+    for set in field(&data, "sets")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let name = field(set, "details")
+            .and_then(|details| string(details, "name"))
+            .or_else(|| string(set, "name"))
+            .unwrap_or_else(|| String::from("Set"));
+        let mut playlist = vec![name];
+        for entry in field(set, "contents")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let id = number(entry, "songid");
+            let song = ids
+                .iter()
+                .find(|(song, _)| id.is_some() && *song == id)
+                .map(|(_, index)| &library.songs[*index]);
+            match song {
+                Some(song) => playlist.push(self::entry(
+                    song,
+                    key(entry),
+                    number(entry, "capo").filter(|capo| *capo > 0),
+                )),
+                None => library.missing.push((
+                    playlist[0].clone(),
+                    id.map_or_else(String::new, |id| id.to_string()),
+                )),
+            }
+        }
+        library.playlists.push(playlist.join("\n") + "\n");
+    }
+    Ok(library)
+}
+
+/// Writes the playlist entry of a song, which names the song like the library does along with
+/// the key it should be played in and the capo it should be played with
+fn entry(song: &str, key: Option<Key>, capo: Option<i64>) -> String {
+    let mut entry = Song::get_name(song).unwrap_or_default();
+    if let Some(key) = key {
+        entry += &format!(" [{}]", key);
+    }
+    if let Some(capo) = capo {
+        entry += &format!(" {{capo: {}}}", capo);
+    }
+    entry
+}
+
+/// Finds a field of a JSON object, ignoring the case of its name since SongBook Pro isn't
+/// consistent about it
+fn field<'a>(value: &'a Value, name: &str) -> Option<&'a Value> {
+    value
+        .as_object()?
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+fn string(value: &Value, name: &str) -> Option<String> {
+    match field(value, name)? {
+        Value::String(string) => Some(string.trim().to_owned()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+fn number(value: &Value, name: &str) -> Option<i64> {
+    match field(value, name)? {
+        Value::Number(number) => number.as_i64(),
+        Value::String(string) => string.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Reads a key written as a name, or as a number of semitones above C for major keys and above
+/// C minor, counting from 12, for minor keys
+fn key(value: &Value) -> Option<Key> {
+    match field(value, "key")? {
+        Value::Number(number) => {
+            let mode = match number.as_u64()? {
+                0..=11 => Mode::Major,
+                12..=23 => Mode::Minor,
+                _ => return None,
+            };
+            Some(Key::new(
+                PitchClass::from_u8((number.as_u64()? % 12) as u8),
+                mode,
+            ))
+        }
+        Value::String(string) => string.parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SONGBOOK: &[u8] = include_bytes!("../../tests/fixtures/songbook.zip");
+    const SONGBOOK_PRO: &[u8] = include_bytes!("../../tests/fixtures/songbook.sbp");
+
+    #[test]
+    fn imports_songbook_backups() {
+        let library = linkesoft(SONGBOOK).unwrap();
+        assert_eq!(library.songs.len(), 2);
+        // Songs without a title are named after their file
+        assert!(library.songs[1].starts_with("{title: untitled}\n"));
+        assert_eq!(
+            library.playlists,
+            vec!["Sunday\nAmazing Grace [A]\nuntitled\n"]
+        );
+        assert_eq!(
+            library.missing,
+            vec![(String::from("Sunday"), String::from("Lost Song"))]
+        );
+    }
+
+    #[test]
+    fn imports_songbook_pro_backups() {
+        let library = songbook_pro(SONGBOOK_PRO).unwrap();
+        assert_eq!(
+            library.songs[0],
+            "{title: Amazing Grace}\n{subtitle: John Newton}\n{key: G}\n{capo: 2}\n\
+             A[G]mazing [C]grace\n"
+        );
+        // Deleted songs are left out, and tags the song already has aren't repeated
+        assert_eq!(library.songs.len(), 2);
+        assert_eq!(library.songs[1].matches("{title:").count(), 1);
+        assert_eq!(
+            library.playlists,
+            vec!["Sunday\nBe Thou My Vision [E]\nAmazing Grace - John Newton\n"]
+        );
+        assert_eq!(
+            library.missing,
+            vec![(String::from("Sunday"), String::from("3"))]
+        );
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(linkesoft(b"Not a backup").is_err());
+        assert!(songbook_pro(b"Not a backup").is_err());
+    }
+
+    #[test]
+    fn reads_minor_keys_as_numbers() {
+        let key = |key: Value| self::key(&serde_json::json!({ "key": key }));
+        assert_eq!(key(Value::from(7)), "G".parse().ok());
+        assert_eq!(key(Value::from(21)), "Am".parse().ok());
+        assert_eq!(key(Value::from(24)), None);
+        assert_eq!(key(Value::from("F#m")), "F#m".parse().ok());
+    }
+
+    #[test]
+    fn writes_capos_into_set_entries() {
+        let song = "{title: Grace}\n[G]Glory\n";
+        assert_eq!(entry(song, None, None), "Grace");
+        assert_eq!(
+            entry(song, "A".parse().ok(), Some(2)),
+            "Grace [A] {capo: 2}"
+        );
+    }
+}
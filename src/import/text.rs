@@ -0,0 +1,263 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+lazy_static! {
+    static ref RE_CHORD: Regex = Regex::new(
+        r"^\(?[ABCDEFG][b#]?(?:maj|min|dim|aug|sus|add|m|M|\+|°|ø|[b#]?[0-9]+|\(|\))*(?:/[ABCDEFG][b#]?)?\)?$"
+    )
+    .unwrap();
+    /// Tokens that may appear on chord lines without being chords
+    static ref RE_FILLER: Regex = Regex::new(r"^(?:\||-+|/|\(?x[0-9]+\)?|N\.?C\.?|%)$").unwrap();
+    static ref RE_BRACKET_HEADER: Regex = Regex::new(r"^\s*\[([^\]]+)\]\s*$").unwrap();
+    static ref RE_LABEL_HEADER: Regex = Regex::new(
+        r"(?i)^\s*((?:pre-?)?(?:intro|verse|chorus|bridge|outro|solo|interlude|instrumental|refrain|tag|coda|hook|ending|break)(?:\s*[0-9]+)?)\s*:?\s*$"
+    )
+    .unwrap();
+    static ref RE_CAPO: Regex = Regex::new(r"(?i)^\s*capo\s*:?\s*([0-9]+)").unwrap();
+    static ref RE_KEY: Regex = Regex::new(r"(?i)^\s*key\s*:?\s*([ABCDEFG][b#]?m?)\s*$").unwrap();
+    static ref RE_UG_TITLE: Regex =
+        Regex::new(r"^(.+?) (?:Chords|Tabs?|Ukulele Chords) by (.+)$").unwrap();
+    /// Markup of the raw format used by Ultimate Guitar
+    static ref RE_UG_MARKUP: Regex = Regex::new(r"\[/?(?:ch|tab)\]").unwrap();
+}
+
+enum Line<'a> {
+    Blank,
+    Chords(&'a str),
+    Header(&'a str),
+    Capo(&'a str),
+    Key(&'a str),
+    Lyrics(&'a str),
+}
+
+impl<'a> Line<'a> {
+    fn from(line: &'a str) -> Self {
+        if line.trim().is_empty() {
+            return Line::Blank;
+        }
+        if is_chord_line(line) {
+            return Line::Chords(line);
+        }
+        if let Some(cap) = RE_BRACKET_HEADER.captures(line) {
+            return Line::Header(cap.get(1).unwrap().as_str().trim());
+        }
+        if let Some(cap) = RE_LABEL_HEADER.captures(line) {
+            return Line::Header(cap.get(1).unwrap().as_str());
+        }
+        if let Some(cap) = RE_CAPO.captures(line) {
+            return Line::Capo(cap.get(1).unwrap().as_str());
+        }
+        if let Some(cap) = RE_KEY.captures(line) {
+            return Line::Key(cap.get(1).unwrap().as_str());
+        }
+        Line::Lyrics(line)
+    }
+}
+
+/// Converts a song with chords on the line above the lyrics into ChordPro
+pub fn import(input: &str) -> String {
+    let input = RE_UG_MARKUP.replace_all(input, "");
+    let lines: Vec<Line> = input
+        .lines()
+        .map(|line| Line::from(line.trim_end()))
+        .collect();
+
+    let (title, subtitle, start) = title(&lines);
+    let mut header = vec![];
+    if let Some(title) = title {
+        header.push(format!("{{title: {}}}", title));
+    }
+    if let Some(subtitle) = subtitle {
+        header.push(format!("{{subtitle: {}}}", subtitle));
+    }
+
+    let mut body: Vec<String> = vec![];
+    let mut chorus = false;
+    let mut i = start;
+    while i < lines.len() {
+        match lines[i] {
+            Line::Blank => {
+                if chorus {
+                    body.push(String::from("{eoc}"));
+                    chorus = false;
+                }
+                body.push(String::new());
+            }
+            Line::Header(name) => {
+                if chorus {
+                    body.push(String::from("{eoc}"));
+                }
+                body.push(format!("{{c: {}}}", name));
+                chorus = is_chorus(name);
+                if chorus {
+                    body.push(String::from("{soc}"));
+                }
+            }
+            Line::Capo(capo) => header.push(format!("{{capo: {}}}", capo)),
+            Line::Key(key) => header.push(format!("{{key: {}}}", key)),
+            Line::Chords(chords) => match lines.get(i + 1) {
+                Some(Line::Lyrics(lyrics)) => {
+                    body.push(merge(chords, lyrics));
+                    i += 1;
+                }
+                _ => body.push(merge(chords, "")),
+            },
+            Line::Lyrics(lyrics) => body.push(lyrics.to_owned()),
+        }
+        i += 1;
+    }
+    if chorus {
+        body.push(String::from("{eoc}"));
+    }
+
+    // Leave at most one empty line between sections
+    body.dedup_by(|a, b| a.is_empty() && b.is_empty());
+    while body.first().is_some_and(String::is_empty) {
+        body.remove(0);
+    }
+    while body.last().is_some_and(String::is_empty) {
+        body.pop();
+    }
+    header.append(&mut body);
+    header.join("\n") + "\n"
+}
+
+/// Finds the title and subtitle at the start of a song, returning the index of the first line
+/// after them. The title is either a line like "Song Chords by Artist", or one or two lines of
+/// text followed by an empty line.
+fn title<'a>(lines: &[Line<'a>]) -> (Option<&'a str>, Option<&'a str>, usize) {
+    let first = match lines.iter().position(|line| !matches!(line, Line::Blank)) {
+        Some(first) => first,
+        None => return (None, None, 0),
+    };
+    if let Line::Lyrics(line) = lines[first] {
+        if let Some(cap) = RE_UG_TITLE.captures(line.trim()) {
+            return (
+                Some(cap.get(1).unwrap().as_str()),
+                Some(cap.get(2).unwrap().as_str()),
+                first + 1,
+            );
+        }
+    }
+    let texts: Vec<&str> = lines[first..]
+        .iter()
+        .map_while(|line| match line {
+            Line::Lyrics(text) => Some(text.trim()),
+            _ => None,
+        })
+        .collect();
+    let ends_in_blank = matches!(lines.get(first + texts.len()), Some(Line::Blank));
+    match (texts.len(), ends_in_blank) {
+        (1, true) => (Some(texts[0]), None, first + 1),
+        (2, true) => (Some(texts[0]), Some(texts[1]), first + 2),
+        _ => (None, None, first),
+    }
+}
+
+fn is_chord_line(line: &str) -> bool {
+    let mut chords = 0;
+    for token in line.split_whitespace() {
+        if RE_CHORD.is_match(token) {
+            chords += 1;
+        } else if !RE_FILLER.is_match(token) {
+            return false;
+        }
+    }
+    chords > 0
+}
+
+fn is_chorus(header: &str) -> bool {
+    let header = header.to_lowercase();
+    header.contains("chorus") && !header.contains("pre")
+}
+
+/// Puts the chords of a chord line into the lyrics below it, at the same columns
+pub fn merge(chords: &str, lyrics: &str) -> String {
+    let mut positions = vec![];
+    let mut start = None;
+    for (column, c) in chords.chars().chain(std::iter::once(' ')).enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(column),
+            (true, Some(from)) => {
+                let token: String = chords.chars().skip(from).take(column - from).collect();
+                if RE_CHORD.is_match(&token) {
+                    let chord = token.trim_start_matches('(').trim_end_matches(')');
+                    positions.push((from, chord.to_owned()));
+                }
+                start = None;
+            }
+            _ => (),
+        }
+    }
+
+    // Chords go before the character below them, which can be two columns wide
+    let mut merged = String::new();
+    let mut column = 0;
+    let mut positions = positions.into_iter().peekable();
+    for grapheme in lyrics.graphemes(true) {
+        let width = grapheme.width();
+        while let Some((_, chord)) = positions.next_if(|(at, _)| *at < column + width.max(1)) {
+            merged += &format!("[{}]", chord);
+        }
+        merged += grapheme;
+        column += width;
+    }
+    for (at, chord) in positions {
+        merged += &" ".repeat(at.saturating_sub(column));
+        merged += &format!("[{}]", chord);
+        column = column.max(at);
+    }
+    merged.trim_end().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHEET: &str = "Amazing Grace Chords by John Newton\n\
+                         Capo: 2\n\
+                         Key: G\n\
+                         \n\
+                         [Verse 1]\n\
+                         G       C      G\n\
+                         Amazing grace, how sweet\n\
+                         \n\
+                         Chorus:\n\
+                         [ch]Em[/ch]     D\n\
+                         I once was lost\n";
+
+    #[test]
+    fn imports_chords_over_lyrics() {
+        assert_eq!(
+            import(SHEET),
+            "{title: Amazing Grace}\n{subtitle: John Newton}\n{capo: 2}\n{key: G}\n\
+             {c: Verse 1}\n[G]Amazing [C]grace, [G]how sweet\n\n\
+             {c: Chorus}\n{soc}\n[Em]I once [D]was lost\n{eoc}\n"
+        );
+    }
+
+    #[test]
+    fn reads_titles_before_an_empty_line() {
+        assert_eq!(
+            import("Grace\nNewton\n\nC\nWords\n"),
+            "{title: Grace}\n{subtitle: Newton}\n[C]Words\n"
+        );
+        assert_eq!(import("C\nWords\n"), "[C]Words\n");
+    }
+
+    #[test]
+    fn detects_chord_lines() {
+        assert!(is_chord_line("G  D/F#  Em7  | Cadd9 (x2)"));
+        assert!(is_chord_line("N.C.  Bbmaj7"));
+        assert!(!is_chord_line("A man walks into a bar"));
+        assert!(!is_chord_line("| -- |"));
+    }
+
+    #[test]
+    fn merges_chords_past_the_end_of_the_lyrics() {
+        assert_eq!(merge("  Am    (G)", "Hi"), "Hi[Am]      [G]");
+        assert_eq!(merge("C  G", ""), "[C]   [G]");
+    }
+}
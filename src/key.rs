@@ -0,0 +1,252 @@
+extern crate rust_music_theory as rustmt;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use rustmt::note::PitchClass;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+lazy_static! {
+    static ref RE_KEY: Regex = Regex::new(r"^([ABCDEFG][b#]?) *([A-Za-z]*)$").unwrap();
+}
+
+const SHARPS: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+const FLATS: [&str; 12] = [
+    "C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B",
+];
+/// Relative major keys that are written with flats
+const FLAT_KEYS: [u8; 6] = [0, 1, 3, 5, 8, 10];
+
+/// The modes a song can be in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Major,
+    Minor,
+    Dorian,
+    Mixolydian,
+}
+
+impl Mode {
+    /// Semitones above the tonic of each scale degree
+    pub fn scale(self) -> [u8; 7] {
+        match self {
+            Mode::Major => [0, 2, 4, 5, 7, 9, 11],
+            Mode::Minor => [0, 2, 3, 5, 7, 8, 10],
+            Mode::Dorian => [0, 2, 3, 5, 7, 9, 10],
+            Mode::Mixolydian => [0, 2, 4, 5, 7, 9, 10],
+        }
+    }
+
+    /// Semitones from the tonic up to the tonic of the relative major key
+    fn relative_major(self) -> u8 {
+        match self {
+            Mode::Major => 0,
+            Mode::Minor => 3,
+            Mode::Dorian => 10,
+            Mode::Mixolydian => 5,
+        }
+    }
+
+    fn from_str(mode: &str) -> Option<Self> {
+        match mode.to_lowercase().as_str() {
+            "" | "maj" | "major" | "ionian" => Some(Mode::Major),
+            "m" | "min" | "minor" | "aeolian" => Some(Mode::Minor),
+            "dor" | "dorian" => Some(Mode::Dorian),
+            "mix" | "mixo" | "mixolydian" => Some(Mode::Mixolydian),
+            _ => None,
+        }
+    }
+}
+
+/// The key of a song, made up of its tonic and mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Key {
+    pub tonic: PitchClass,
+    pub mode: Mode,
+}
+
+impl Key {
+    pub fn new(tonic: PitchClass, mode: Mode) -> Self {
+        Key { tonic, mode }
+    }
+
+    /// Finds the key with a key signature of this many sharps, or flats when negative, as used
+    /// by notation software
+    pub fn from_fifths(fifths: i32, minor: bool) -> Self {
+        let key = Key::new(
+            PitchClass::from_u8((fifths * 7).rem_euclid(12) as u8),
+            Mode::Major,
+        );
+        match minor {
+            true => key.relative(),
+            false => key,
+        }
+    }
+
+    /// Moves the key up by a number of semitones, or down when negative
+    pub fn transpose(self, semitones: i32) -> Self {
+        Key::new(
+            PitchClass::from_u8((self.tonic.into_u8() as i32 + semitones).rem_euclid(12) as u8),
+            self.mode,
+        )
+    }
+
+    /// Returns the major or minor key that shares its key signature with this key
+    pub fn relative(self) -> Self {
+        match self.mode {
+            Mode::Minor => Key::new(self.relative_major(), Mode::Major),
+            _ => Key::new(
+                PitchClass::from_u8(self.relative_major().into_u8() + 9),
+                Mode::Minor,
+            ),
+        }
+    }
+
+    /// Returns the tonic of the major key that shares its key signature with this key
+    pub fn relative_major(self) -> PitchClass {
+        PitchClass::from_u8(self.tonic.into_u8() + self.mode.relative_major())
+    }
+
+    /// Writes a note with sharps or flats, depending on the key signature
+    pub fn spell(self, note: PitchClass) -> String {
+        match FLAT_KEYS.contains(&self.relative_major().into_u8()) {
+            true => FLATS[note.into_u8() as usize].to_owned(),
+            false => SHARPS[note.into_u8() as usize].to_owned(),
+        }
+    }
+
+    /// Finds the lowest capo fret that lets a song in this key be played with open shapes, along
+    /// with the key of those shapes
+    pub fn open_shape_capo(self) -> Option<(i32, Key)> {
+        (0..8)
+            .map(|capo| (capo, self.transpose(-capo)))
+            .find(|(_, shape_key)| shape_key.is_open())
+    }
+
+    /// Whether the tonic chord and common progressions of this key can be played with open
+    /// shapes
+    fn is_open(self) -> bool {
+        let open_tonics: &[PitchClass] = match self.mode {
+            Mode::Major | Mode::Mixolydian => &[
+                PitchClass::C,
+                PitchClass::D,
+                PitchClass::E,
+                PitchClass::G,
+                PitchClass::A,
+            ],
+            Mode::Minor | Mode::Dorian => &[PitchClass::D, PitchClass::E, PitchClass::A],
+        };
+        open_tonics.contains(&self.tonic)
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    /// Parses keys like `A`, `Bb`, `F#m`, `A minor` and `D dorian`
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        let parse = || {
+            let caps = RE_KEY.captures(key.trim())?;
+            let tonic = PitchClass::from_str(caps.get(1).unwrap().as_str())?;
+            let mode = caps.get(2).unwrap().as_str();
+            // A lone capital M means major, as in "CM"
+            let mode = match mode {
+                "M" => Mode::Major,
+                mode => Mode::from_str(mode)?,
+            };
+            Some(Key::new(tonic, mode))
+        };
+        parse().ok_or(format!("Unknown key '{}'", key))
+    }
+}
+
+/// Keys are serialized as their name, like `F#m`
+impl Serialize for Key {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D>(deserializer: D) -> Result<Key, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tonic = self.spell(self.tonic);
+        match self.mode {
+            Mode::Major => write!(f, "{}", tonic),
+            Mode::Minor => write!(f, "{}m", tonic),
+            Mode::Dorian => write!(f, "{} dorian", tonic),
+            Mode::Mixolydian => write!(f, "{} mixolydian", tonic),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: &str) -> Key {
+        key.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_keys_with_modes() {
+        assert_eq!(key("Bb"), Key::new(PitchClass::As, Mode::Major));
+        assert_eq!(key("F#m"), Key::new(PitchClass::Fs, Mode::Minor));
+        assert_eq!(key("A minor"), Key::new(PitchClass::A, Mode::Minor));
+        assert_eq!(key("CM"), Key::new(PitchClass::C, Mode::Major));
+        assert_eq!(key("D dorian").mode, Mode::Dorian);
+        assert_eq!(key("G mix").mode, Mode::Mixolydian);
+        assert!("H".parse::<Key>().is_err());
+        assert!("C lydian".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn spells_notes_for_the_key_signature() {
+        assert_eq!(key("Bb").to_string(), "Bb");
+        assert_eq!(key("F#m").to_string(), "F#m");
+        assert_eq!(key("Dm").spell(PitchClass::As), "Bb");
+        assert_eq!(key("E").spell(PitchClass::As), "A#");
+        assert_eq!(key("D dorian").to_string(), "D dorian");
+        assert_eq!(key("B mixolydian").spell(PitchClass::As), "A#");
+    }
+
+    #[test]
+    fn transposes_and_relates_keys() {
+        assert_eq!(key("A").transpose(-3), key("F#"));
+        assert_eq!(key("Am").transpose(14), key("Bm"));
+        assert_eq!(key("C").relative(), key("Am"));
+        assert_eq!(key("F#m").relative(), key("A"));
+        assert_eq!(key("D dorian").relative(), key("Am"));
+    }
+
+    #[test]
+    fn finds_capos_for_open_shapes() {
+        assert_eq!(key("C").open_shape_capo(), Some((0, key("C"))));
+        assert_eq!(key("F").open_shape_capo(), Some((1, key("E"))));
+        assert_eq!(key("Bb").open_shape_capo(), Some((1, key("A"))));
+        assert_eq!(key("Cm").open_shape_capo(), Some((3, key("Am"))));
+    }
+
+    #[test]
+    fn finds_keys_from_fifths() {
+        assert_eq!(Key::from_fifths(0, false), key("C"));
+        assert_eq!(Key::from_fifths(-1, false), key("F"));
+        assert_eq!(Key::from_fifths(3, true), key("F#m"));
+        assert_eq!(Key::from_fifths(-6, false), key("Gb"));
+    }
+}
@@ -0,0 +1,21 @@
+//! Reading, transposing and exporting songs in ChordPro format.
+//!
+//! Songs are parsed with [`parser::Song`], which can be transposed with
+//! [`parser::Song::change_key`] and [`parser::Song::set_key`]. A directory of songs and playlists
+//! is read with [`library::Library::scan`], and songs can be exported with [`export::export`] or
+//! imported from other formats with [`import::import`].
+//!
+//! The terminal interface is drawn by [`ui::draw`] from the state in [`app::App`], which works
+//! with any `tui` backend, including `tui::backend::TestBackend` for rendering without a
+//! terminal.
+
+pub mod app;
+pub mod conf;
+pub mod diagram;
+pub mod export;
+pub mod import;
+pub mod key;
+pub mod library;
+pub mod notation;
+pub mod parser;
+pub mod ui;
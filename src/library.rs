@@ -0,0 +1,301 @@
+use crate::{
+    import,
+    parser::{Playlist, Selection, Song, MAX_CAPO},
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{self, DirEntry, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+lazy_static! {
+    static ref RE_SONG_TRANSPOSITION: Regex = Regex::new(r" \[([ABCDEFG][b#]?m?)\]").unwrap();
+    static ref RE_SONG_CAPO: Regex = Regex::new(r" \{capo: *(\d+)\}$").unwrap();
+}
+
+/// The songs, playlists and folders found in a library directory, along with the contents of
+/// the songs and playlists
+#[derive(Default)]
+pub struct Library {
+    files: HashMap<FileType, String>,
+    /// The entries found in every file and folder, so folders can be listed without reading
+    /// and importing their files again
+    entries: Vec<(PathBuf, FileType)>,
+    errors: Vec<(PathBuf, String)>,
+    selection: Selection,
+}
+
+impl Library {
+    /// Reads every song and playlist in a directory and its subdirectories. Songs that can't be
+    /// read or imported are left out and listed in [`Library::errors`].
+    ///
+    /// Songs are read for no instrument or user until [`Library::with_selection`] is given one,
+    /// such as [`Config::selection`](crate::conf::Config::selection) for the configured guitar.
+    pub fn scan(path: &Path) -> Self {
+        let mut library = Library::default();
+        for file in Library::get_direntries(path) {
+            let path = file.path();
+            let name = file.file_name().to_string_lossy().into_owned();
+            let files = if path.is_dir() {
+                vec![(FileType::Folder(path.clone()), String::new())]
+            } else {
+                match read_song(&path) {
+                    Ok(Some(filestring)) => songs(&filestring, &name),
+                    Ok(None) if name.ends_with(".lst") => {
+                        let filestring = fs::read_to_string(&path).unwrap_or_default();
+                        vec![(
+                            FileType::Playlist(Playlist::get_name(&filestring).unwrap_or(name)),
+                            filestring,
+                        )]
+                    }
+                    Ok(None) => vec![],
+                    Err(error) => {
+                        library.errors.push((path, error.to_string()));
+                        continue;
+                    }
+                }
+            };
+            for (file, contents) in files {
+                library.entries.push((path.clone(), file.clone()));
+                library.files.insert(file, contents);
+            }
+        }
+        library
+    }
+
+    /// Reads the songs of the library for an instrument and user, which decides the directives
+    /// with selectors that count
+    pub fn with_selection(self, selection: Selection) -> Self {
+        Library { selection, ..self }
+    }
+
+    /// Returns every file in the library with its contents, which are ChordPro for songs
+    pub fn files(&self) -> &HashMap<FileType, String> {
+        &self.files
+    }
+
+    /// Returns the songs, playlists and folders directly inside a folder of the library, sorted
+    /// by name
+    pub fn folder(&self, path: &Path) -> Vec<FileType> {
+        let mut files: Vec<FileType> = self
+            .entries
+            .iter()
+            .filter(|(entry, _)| entry.parent() == Some(path))
+            .map(|(_, file)| file.clone())
+            .collect();
+        files.sort_by_key(FileType::name);
+        files
+    }
+
+    /// Returns the files that look like songs but couldn't be read or imported, with the reason
+    pub fn errors(&self) -> &[(PathBuf, String)] {
+        &self.errors
+    }
+
+    /// Looks up a song in the library by name. Names of songs in playlists may end in a key
+    /// between brackets, in which case the song is transposed to that key, and in a capo like
+    /// `{capo: 2}`.
+    pub fn find_song(&self, name: &str) -> Option<Song> {
+        if let Some(capo) = RE_SONG_CAPO.captures(name) {
+            let mut song = self.find_song(&RE_SONG_CAPO.replace(name, ""))?;
+            song.capo = capo
+                .get(1)
+                .unwrap()
+                .as_str()
+                .parse::<i32>()
+                .ok()?
+                .min(MAX_CAPO);
+            return Some(song);
+        }
+        if let Some(song) = self.files.get(&FileType::Song(name.to_owned())) {
+            return Some(Song::parse(song.clone(), None, &self.selection));
+        }
+        let key = RE_SONG_TRANSPOSITION.captures(name)?;
+        let actual_name = RE_SONG_TRANSPOSITION.replace(name, "");
+        let song = self.files.get(&FileType::Song(actual_name.to_string()))?;
+        Some(Song::parse(
+            song.clone(),
+            Some(key.get(1).unwrap().as_str().parse().ok()?),
+            &self.selection,
+        ))
+    }
+
+    /// Returns every song in the library along with its name, sorted by name
+    pub fn songs(&self) -> Vec<(String, Song)> {
+        let mut songs: Vec<(String, Song)> = self
+            .files
+            .iter()
+            .filter_map(|(file, contents)| match file {
+                FileType::Song(name) => Some((
+                    name.clone(),
+                    Song::parse(contents.clone(), None, &self.selection),
+                )),
+                _ => None,
+            })
+            .collect();
+        songs.sort_by(|a, b| a.0.cmp(&b.0));
+        songs
+    }
+
+    /// Returns every playlist in the library, sorted by title
+    pub fn playlists(&self) -> Vec<Playlist> {
+        let mut playlists: Vec<Playlist> = self
+            .files
+            .iter()
+            .filter_map(|(file, contents)| match file {
+                FileType::Playlist(_) => Some(Playlist::from(contents)),
+                _ => None,
+            })
+            .collect();
+        playlists.sort_by(|a, b| a.title.cmp(&b.title));
+        playlists
+    }
+
+    // Gets all DirEntry's that are not a folder
+    fn get_direntries(path: &Path) -> Vec<DirEntry> {
+        let dir = match fs::read_dir(path) {
+            Ok(d) => d,
+            Err(_) => return vec![],
+        };
+        dir.flat_map(|dir| {
+            let dir = dir.unwrap();
+            let path = dir.path();
+            if path.is_dir() {
+                let mut dirs = Library::get_direntries(&path);
+                dirs.push(dir);
+                dirs
+            } else {
+                vec![dir]
+            }
+        })
+        .collect()
+    }
+}
+
+/// Reads a song as ChordPro. Songs are ChordPro files ending in `.txt`, or files in a format
+/// that can be imported, like OpenSong files, which usually have no extension. Returns `None`
+/// for files that aren't songs, and an error for songs that can't be read or imported.
+pub fn read_song(path: &Path) -> Result<Option<String>, Box<dyn Error>> {
+    let format = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("txt") => return Ok(Some(fs::read_to_string(path)?)),
+        Some("xml") | None => {
+            // Only the start of a file is needed to tell whether it is a song
+            let mut start = vec![];
+            File::open(path)?.take(512).read_to_end(&mut start)?;
+            match import::Format::detect(path, &start) {
+                format @ (import::Format::OpenSong | import::Format::MusicXml) => format,
+                _ => return Ok(None),
+            }
+        }
+        Some("gp3" | "gp4" | "gp5") => import::Format::GuitarPro,
+        Some("musicxml" | "mxl") => import::Format::MusicXml,
+        _ => return Ok(None),
+    };
+    Ok(Some(import::import(&fs::read(path)?, format)?))
+}
+
+/// Splits a file into its songs, which are named after their titles or after the file
+fn songs(filestring: &str, file_name: &str) -> Vec<(FileType, String)> {
+    let songs = Song::split(filestring);
+    let count = songs.len();
+    songs
+        .into_iter()
+        .enumerate()
+        .map(|(index, song)| {
+            let name = Song::get_name(&song).unwrap_or_else(|| match count {
+                1 => file_name.to_string(),
+                _ => format!("{} ({})", file_name, index + 1),
+            });
+            (FileType::Song(name), song)
+        })
+        .collect()
+}
+
+/// An entry in the library. Songs and playlists are known by their name, which is how
+/// playlists refer to songs.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FileType {
+    Folder(PathBuf),
+    Song(String),
+    Playlist(String),
+}
+
+impl FileType {
+    /// The name of the file as shown in the library, which is the name of the folder, song or
+    /// playlist
+    pub fn name(&self) -> String {
+        match self {
+            FileType::Folder(path) => path.file_name().unwrap().to_str().unwrap().to_owned(),
+            FileType::Song(name) => name.to_owned(),
+            FileType::Playlist(name) => name.to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_opensong_songs_without_an_extension() {
+        let dir = std::env::temp_dir().join(format!("gpro-opensong-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("Grace"),
+            "<song><title>Grace</title><lyrics>.G\n Glory</lyrics></song>",
+        )
+        .unwrap();
+        fs::write(dir.join("notes"), "Just some words").unwrap();
+        let song = read_song(&dir.join("Grace"));
+        let notes = read_song(&dir.join("notes"));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            song.unwrap().as_deref(),
+            Some("{title: Grace}\n\n[G]Glory\n")
+        );
+        assert_eq!(notes.unwrap(), None);
+    }
+
+    #[test]
+    fn names_the_songs_of_a_file() {
+        let names = |filestring: &str| -> Vec<String> {
+            songs(filestring, "hymns.cho")
+                .iter()
+                .map(|(file, _)| file.name())
+                .collect()
+        };
+        assert_eq!(
+            names("{title: One}\n{ns}\n[G]Sing\n{ns}\n[C]On"),
+            ["One", "hymns.cho (2)", "hymns.cho (3)"]
+        );
+        assert_eq!(names("[G]Sing\n"), ["hymns.cho"]);
+    }
+
+    #[test]
+    fn lists_folders_from_the_scan() {
+        let dir = std::env::temp_dir().join(format!("gpro-folders-{}", std::process::id()));
+        fs::create_dir_all(dir.join("hymns")).unwrap();
+        fs::write(
+            dir.join("hymns").join("grace.txt"),
+            "{title: Grace}\n[G]Glory",
+        )
+        .unwrap();
+        fs::write(dir.join("song.gp5"), "Not Guitar Pro").unwrap();
+        fs::write(dir.join("notes"), "Just some words").unwrap();
+        let library = Library::scan(&dir);
+        // Files aren't read again when a folder is listed
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(library.folder(&dir), [FileType::Folder(dir.join("hymns"))]);
+        assert_eq!(
+            library.folder(&dir.join("hymns")),
+            [FileType::Song(String::from("Grace"))]
+        );
+        let errors: Vec<&PathBuf> = library.errors().iter().map(|(path, _)| path).collect();
+        assert_eq!(errors, [&dir.join("song.gp5")]);
+    }
+}
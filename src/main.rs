@@ -1,25 +1,31 @@
-mod app;
-mod conf;
-mod parser;
-mod ui;
 mod util;
 
-use crate::{
+use crate::util::{Event, Events};
+use getopts::Options;
+use gpro::{
     app::{App, AppState},
     conf::Config,
-    util::{Event, Events},
+    export::{self, Format},
+    import, key,
+    library::Library,
+    parser::{Playlist, Song},
+    ui,
 };
-use getopts::Options;
-use std::{env, error::Error, io, path::PathBuf, time::Duration};
-use termion::{event::Key, raw::IntoRawMode};
-use tui::{
-    backend::TermionBackend,
-    layout::{Constraint, Direction, Layout},
-    Terminal,
+use std::{
+    env,
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
 };
+use termion::{event::Key, raw::IntoRawMode};
+use tui::{backend::TermionBackend, Terminal};
 
 fn print_usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} FILE [options]", program);
+    let brief = format!(
+        "Usage: {0} [options]\n       {0} export FILE... [options]\n       {0} import FILE... [options]\n       {0} dump FILE... [--json]",
+        program
+    );
     print!("{}", opts.usage(&brief));
 }
 
@@ -31,6 +37,23 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut opts = Options::new();
     opts.optopt("c", "config", "set config file", "PATH");
     opts.optopt("", "default-config", "write the default config", "PATH");
+    opts.optopt(
+        "f",
+        "format",
+        "set the export format (pdf, html, txt, md, tex, openlyrics, opensong) or import format (txt, openlyrics, opensong, gp, musicxml, songbook, sbp)",
+        "FORMAT",
+    );
+    opts.optopt("k", "key", "set the key to export songs in", "KEY");
+    opts.optopt(
+        "w",
+        "width",
+        "set the line width of text exports",
+        "COLUMNS",
+    );
+    opts.optflag("", "library", "export the whole library");
+    opts.optopt("o", "output", "set the file to export or import to", "PATH");
+    opts.optflag("y", "yes", "save imported songs without previewing them");
+    opts.optflag("", "json", "dump songs as JSON");
     opts.optflag("h", "help", "print this help menu");
     opts.optflag("d", "debug", "");
 
@@ -73,8 +96,23 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     };
 
+    if matches.free.first().map(String::as_str) == Some("export") {
+        return export(&matches, config);
+    }
+
+    if matches.free.first().map(String::as_str) == Some("dump") {
+        return dump(&matches, &config);
+    }
+
     let mut app = App::new(config.clone());
 
+    if matches.free.first().map(String::as_str) == Some("import") {
+        match import(&matches, &config)? {
+            Some((path, chordpro)) => app.preview_import(path, chordpro),
+            None => return Ok(()),
+        }
+    }
+
     if matches.opt_present("d") {
         return Ok(());
     }
@@ -89,32 +127,9 @@ fn main() -> Result<(), Box<dyn Error>> {
     });
 
     term.clear().unwrap();
+    let mut imported = None;
     loop {
-        term.draw(|f| {
-            let layout = Layout::default()
-                .direction(Direction::Horizontal)
-                .margin(1)
-                .constraints([Constraint::Length(20), Constraint::Min(80)].as_ref())
-                .split(f.size());
-
-            let left_bar = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Max(100), Constraint::Length(3)])
-                .split(layout[0]);
-
-            match app.state {
-                AppState::Default => ui::draw_song_list(f, &mut app, layout[0]),
-                AppState::Searching => {
-                    ui::draw_song_list(f, &mut app, left_bar[0]);
-                    ui::draw_search_bar(f, &mut app, left_bar[1]);
-                }
-                AppState::Transposing => {
-                    ui::draw_song_list(f, &mut app, left_bar[0]);
-                    ui::draw_transposition(f, &mut app, left_bar[1]);
-                }
-            }
-            ui::draw_song(f, &app, layout[1]);
-        })?;
+        term.draw(|f| ui::draw(f, &mut app))?;
 
         match events.next()? {
             Event::Input(key) => {
@@ -126,10 +141,11 @@ fn main() -> Result<(), Box<dyn Error>> {
                         if key == app.config.keybinds.search.to_key() {
                             app.state = AppState::Searching
                         } else if key == app.config.keybinds.transpose.to_key() {
-                            app.state = AppState::Transposing;
+                            app.open_key_picker();
                         }
                         keybinds_songlist(&key, &mut app);
                         keybinds_song(&key, &mut app);
+                        keybinds_chords(&key, &mut app);
                     }
                     AppState::Searching => {
                         if key == Key::Esc {
@@ -157,25 +173,309 @@ fn main() -> Result<(), Box<dyn Error>> {
                             app.state = AppState::Default
                         } else if key == app.config.keybinds.search.to_key() {
                             app.state = AppState::Searching
-                        } else if key == app.config.keybinds.next.to_key() {
-                            if let Some(song) = &mut app.song {
-                                song.change_key(1)
-                            }
-                        } else if key == app.config.keybinds.back.to_key() {
-                            if let Some(song) = &mut app.song {
-                                song.change_key(-1)
-                            }
+                        } else if key == app.config.keybinds.down.to_key() {
+                            app.key_picker_forward(1)
+                        } else if key == app.config.keybinds.up.to_key() {
+                            app.key_picker_back(1)
+                        } else if key == app.config.keybinds.next.to_key() || key == Key::Char('\n')
+                        {
+                            app.pick_key()
                         }
                         keybinds_song(&key, &mut app);
+                        keybinds_chords(&key, &mut app);
+                    }
+                    AppState::Importing => {
+                        if key == Key::Esc {
+                            break;
+                        } else if key == Key::Char('\n') {
+                            imported = app.save_import()?;
+                            break;
+                        }
+                        keybinds_song(&key, &mut app);
+                        keybinds_chords(&key, &mut app);
                     }
                 }
             }
             Event::Tick => (),
         }
     }
+
+    // Leave raw mode before printing
+    drop(term);
+    if app.state == AppState::Importing {
+        match imported {
+            Some(path) => println!("Imported to {}", path.display()),
+            None => println!("Import cancelled"),
+        }
+    }
+    warn_unreadable(&app.library);
+    Ok(())
+}
+
+/// Converts the files given on the command line into ChordPro songs. A single song is returned
+/// with the file it will be saved to, so it can be previewed first.
+fn import(
+    matches: &getopts::Matches,
+    config: &Config,
+) -> Result<Option<(PathBuf, String)>, Box<dyn Error>> {
+    let format = match matches.opt_str("f") {
+        Some(format) => Some(format.parse::<import::Format>()?),
+        None => None,
+    };
+    let files = &matches.free[1..];
+    if files.is_empty() {
+        return Err(Box::from("No files to import"));
+    }
+    if files.len() > 1 && !matches.opt_present("y") {
+        return Err(Box::from(
+            "Only one song can be previewed at a time, use --yes to import several files",
+        ));
+    }
+    if files.len() > 1 && matches.opt_present("o") {
+        return Err(Box::from(
+            "--output can only be used when importing one file",
+        ));
+    }
+
+    for file in files {
+        let path = PathBuf::from(file);
+        let contents = fs::read(&path)?;
+        let format = format.unwrap_or_else(|| import::Format::detect(&path, &contents));
+        if format.is_library() {
+            let library = import::import_library(&contents, format)?;
+            let output = matches
+                .opt_str("o")
+                .map_or_else(|| config.path.clone(), PathBuf::from);
+            import_library(library, &output)?;
+            continue;
+        }
+        let chordpro = import::import(&contents, format)?;
+        let output = match matches.opt_str("o") {
+            Some(output) => PathBuf::from(output),
+            None => {
+                let song = Song::parse(chordpro.clone(), None, &config.selection());
+                let name = match song.title.is_empty() {
+                    true => path.file_stem().unwrap().to_string_lossy().into_owned(),
+                    false => song.title,
+                };
+                config.path.join(format!("{}.txt", file_name(&name)))
+            }
+        };
+        if output.exists() {
+            return Err(Box::from(format!("{} already exists", output.display())));
+        }
+        if !matches.opt_present("y") {
+            return Ok(Some((output, chordpro)));
+        }
+        fs::write(&output, chordpro)?;
+        println!("Imported to {}", output.display());
+    }
+    Ok(None)
+}
+
+/// Turns the title of a song or playlist into a file name, so titles like `AC/DC` or `../x`
+/// can't name a file in another directory
+fn file_name(title: &str) -> String {
+    match title.trim() {
+        "" => String::from("Untitled"),
+        title => title.replace(['/', '\\'], "-"),
+    }
+}
+
+/// Saves the songs and playlists of a library backup into a directory. Songs that are already
+/// in the directory are left as they are.
+fn import_library(library: import::Backup, output: &Path) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(output)?;
+    let mut songs = 0;
+    for chordpro in library.songs.iter() {
+        let name = Song::get_name(chordpro).unwrap_or_else(|| String::from("Untitled"));
+        let path = output.join(format!("{}.txt", file_name(&name)));
+        if path.exists() {
+            eprintln!("{} already exists, skipping", path.display());
+            continue;
+        }
+        fs::write(&path, chordpro)?;
+        songs += 1;
+    }
+    let mut playlists = 0;
+    for playlist in library.playlists.iter() {
+        let name = Playlist::get_name(playlist).unwrap_or_default();
+        let path = output.join(format!("{}.lst", file_name(&name)));
+        if path.exists() {
+            eprintln!("{} already exists, skipping", path.display());
+            continue;
+        }
+        fs::write(&path, playlist)?;
+        playlists += 1;
+    }
+    for (set, song) in library.missing.iter() {
+        eprintln!("Song '{}' of set '{}' not found", song, set);
+    }
+    println!(
+        "Imported {} songs and {} playlists to {}",
+        songs,
+        playlists,
+        output.display()
+    );
+    Ok(())
+}
+
+/// Exports the songs and playlists given on the command line into a single file
+fn export(matches: &getopts::Matches, config: Config) -> Result<(), Box<dyn Error>> {
+    let format = matches.opt_str("f").unwrap_or_else(|| String::from("pdf"));
+    let format: Format = format.parse()?;
+    let key = match matches.opt_str("k") {
+        Some(key) => Some(key.parse::<key::Key>()?),
+        None => None,
+    };
+    let width = matches.opt_get_default("w", 80)?;
+    let files = &matches.free[1..];
+
+    let mut songs;
+    let title;
+    let default_output;
+    if matches.opt_present("library") {
+        let library = Library::scan(&config.path).with_selection(config.selection());
+        warn_unreadable(&library);
+        if format == Format::Html {
+            let output = PathBuf::from(
+                matches
+                    .opt_str("o")
+                    .unwrap_or_else(|| String::from("songbook")),
+            );
+            let playlists: Vec<(Playlist, Vec<Song>)> = library
+                .playlists()
+                .into_iter()
+                .map(|playlist| {
+                    let songs = playlist_songs(&library, &playlist);
+                    (playlist, songs)
+                })
+                .collect();
+            export::export_site(&library.songs(), &playlists, &output, &config)?;
+            println!("Exported to {}", output.display());
+            return Ok(());
+        }
+        songs = library.songs().into_iter().map(|(_, song)| song).collect();
+        title = Some(String::from("Songbook"));
+        default_output = PathBuf::from("songbook");
+    } else {
+        if files.is_empty() {
+            return Err(Box::from("No files to export"));
+        }
+        (songs, title) = read_songs(files, &config)?;
+        default_output = PathBuf::from(&files[0]).with_extension("");
+    }
+
+    change_keys(&mut songs, key);
+
+    let output = matches.opt_str("o").map_or(default_output, PathBuf::from);
+    let output = if format.single_song() && songs.len() > 1 {
+        // Every song gets its own file in the output directory
+        export::export_each(&songs, &output, format, width, &config)?;
+        output
+    } else {
+        let output = match matches.opt_present("o") {
+            true => output,
+            false => output.with_extension(format.extension()),
+        };
+        fs::write(
+            &output,
+            export::export(&songs, title.as_deref(), format, width, &config),
+        )?;
+        output
+    };
+    println!("Exported to {}", output.display());
+    Ok(())
+}
+
+/// Prints the parsed songs of the files given on the command line, as JSON with `--json`
+fn dump(matches: &getopts::Matches, config: &Config) -> Result<(), Box<dyn Error>> {
+    let files = &matches.free[1..];
+    if files.is_empty() {
+        return Err(Box::from("No files to dump"));
+    }
+    let key = match matches.opt_str("k") {
+        Some(key) => Some(key.parse::<key::Key>()?),
+        None => None,
+    };
+    let (mut songs, _) = read_songs(files, config)?;
+    change_keys(&mut songs, key);
+
+    let output = match (matches.opt_present("json"), songs.as_slice()) {
+        (true, [song]) => serde_json::to_string_pretty(song)?,
+        (true, songs) => serde_json::to_string_pretty(songs)?,
+        (false, songs) => format!("{:#?}", songs),
+    };
+    match matches.opt_str("o") {
+        Some(path) => fs::write(path, output + "\n")?,
+        None => println!("{}", output),
+    }
     Ok(())
 }
 
+/// Reads the songs in the given files, looking up the songs of playlists in the library. Returns
+/// the title of the last playlist along with the songs.
+fn read_songs(
+    files: &[String],
+    config: &Config,
+) -> Result<(Vec<Song>, Option<String>), Box<dyn Error>> {
+    let mut songs = vec![];
+    let mut title = None;
+    for file in files {
+        let path = PathBuf::from(file);
+        let contents = fs::read_to_string(&path)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("lst") => {
+                let playlist = Playlist::from(&contents);
+                let library = Library::scan(&config.path).with_selection(config.selection());
+                warn_unreadable(&library);
+                songs.append(&mut playlist_songs(&library, &playlist));
+                title = Some(playlist.title);
+            }
+            _ => songs.extend(
+                Song::split(&contents)
+                    .into_iter()
+                    .map(|song| Song::parse(song, None, &config.selection())),
+            ),
+        }
+    }
+    Ok((songs, title))
+}
+
+/// Transposes songs into the given key, warning about songs whose key is unknown
+fn change_keys(songs: &mut [Song], key: Option<key::Key>) {
+    if let Some(key) = key {
+        for song in songs.iter_mut() {
+            if song.key.is_none() {
+                eprintln!("Song '{}' has no key and was not transposed", song.title);
+            }
+            song.set_key(key);
+        }
+    }
+}
+
+/// Warns about songs in the library that couldn't be read or imported
+fn warn_unreadable(library: &Library) {
+    for (path, error) in library.errors() {
+        eprintln!("Unable to read {}: {}", path.display(), error);
+    }
+}
+
+/// Looks up the songs of a playlist in the library, warning about songs that can't be found
+fn playlist_songs(library: &Library, playlist: &Playlist) -> Vec<Song> {
+    playlist
+        .songs
+        .iter()
+        .filter_map(|song| {
+            let found = library.find_song(&song.name());
+            if found.is_none() {
+                eprintln!("Song '{}' was not found in the library", song.name());
+            }
+            found
+        })
+        .collect()
+}
+
 fn keybinds_songlist(key: &Key, app: &mut App) {
     if key == &app.config.keybinds.down.to_key() {
         app.get_nav_mut().forward(1);
@@ -212,3 +512,17 @@ fn keybinds_song(key: &Key, app: &mut App) {
         app.config.extra_column_size -= 1;
     }
 }
+
+fn keybinds_chords(key: &Key, app: &mut App) {
+    if key == &app.config.keybinds.capo_inc.to_key() {
+        app.change_capo(1);
+    } else if key == &app.config.keybinds.capo_dec.to_key() {
+        app.change_capo(-1);
+    } else if key == &app.config.keybinds.capo_suggest.to_key() {
+        app.suggest_capo();
+    } else if key == &app.config.keybinds.chord_display.to_key() {
+        app.config.chord_display = app.config.chord_display.next();
+    } else if key == &app.config.keybinds.chord_notation.to_key() {
+        app.config.chord_notation = app.config.chord_notation.next();
+    }
+}
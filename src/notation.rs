@@ -0,0 +1,314 @@
+extern crate rust_music_theory as rustmt;
+
+use crate::{
+    conf::ChordNotation,
+    key::{Key, Mode},
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use rustmt::note::PitchClass;
+
+lazy_static! {
+    static ref RE_LETTER_CHORD: Regex =
+        Regex::new(r"^([ABCDEFG][b#]?)([^/]*)(?:/([ABCDEFG][b#]?))?$").unwrap();
+    static ref RE_NUMBER_CHORD: Regex =
+        Regex::new(r"^([b#]?)([1-7])([^/]*)(?:/([b#]?)([1-7]))?$").unwrap();
+    static ref RE_ROMAN_CHORD: Regex = Regex::new(&format!(
+        r"^([b#]?)({numeral})([^/]*)(?:/([b#]?)({numeral}))?$",
+        numeral = "VII|VI|IV|V|III|II|I|vii|vi|iv|v|iii|ii|i"
+    ))
+    .unwrap();
+}
+
+const NUMERALS: [&str; 7] = ["I", "II", "III", "IV", "V", "VI", "VII"];
+
+/// Finds the scale degree of a note that lies the given amount of semitones above the tonic,
+/// along with the accidental needed if the note isn't in the scale of the mode
+fn degree(semitones: u8, mode: Mode) -> (&'static str, usize) {
+    let scale = mode.scale();
+    let position = |semitones: u8| scale.iter().position(|s| *s == semitones);
+    // Scales have no gaps larger than a whole step, so a note that isn't in the scale is a flat
+    // of the note above, or a sharp of the note below when the note above is the octave, as the
+    // major seventh is in modes with a flat seventh
+    match (position(semitones), position(semitones + 1)) {
+        (Some(degree), _) => ("", degree),
+        (None, Some(degree)) => ("b", degree),
+        (None, None) => ("#", position(semitones - 1).unwrap()),
+    }
+}
+
+/// Renders a chord written with letter names relative to the given key. Chords that can't be
+/// parsed are returned unchanged.
+pub fn to_notation(chord: &str, key: Key, notation: ChordNotation) -> String {
+    let caps = match RE_LETTER_CHORD.captures(chord) {
+        Some(caps) => caps,
+        None => return chord.to_owned(),
+    };
+    let note_degree = |note: &str| {
+        PitchClass::from_str(note).map(|note| {
+            let semitones = (note.into_u8() as i32 - key.tonic.into_u8() as i32).rem_euclid(12);
+            degree(semitones as u8, key.mode)
+        })
+    };
+    let root = match note_degree(caps.get(1).unwrap().as_str()) {
+        Some(root) => root,
+        None => return chord.to_owned(),
+    };
+    let quality = caps.get(2).unwrap().as_str();
+    let bass = caps.get(3).and_then(|bass| note_degree(bass.as_str()));
+
+    match notation {
+        ChordNotation::Letters => chord.to_owned(),
+        ChordNotation::Nashville => {
+            let number =
+                |(accidental, degree): (&str, usize)| format!("{}{}", accidental, degree + 1);
+            let mut number_chord = format!("{}{}", number(root), quality);
+            if let Some(bass) = bass {
+                number_chord += &format!("/{}", number(bass));
+            }
+            number_chord
+        }
+        ChordNotation::Roman => {
+            let numeral =
+                |(accidental, degree): (&str, usize)| format!("{}{}", accidental, NUMERALS[degree]);
+            let mut numeral_chord = numeral(root);
+            let quality = if let Some(rest) = quality.strip_prefix("dim") {
+                numeral_chord = numeral_chord.to_lowercase();
+                format!("°{}", rest)
+            } else if quality.starts_with('m') && !quality.starts_with("maj") {
+                numeral_chord = numeral_chord.to_lowercase();
+                quality[1..].to_owned()
+            } else {
+                quality.to_owned()
+            };
+            numeral_chord += &quality;
+            if let Some(bass) = bass {
+                numeral_chord += &format!("/{}", numeral(bass));
+            }
+            numeral_chord
+        }
+    }
+}
+
+/// Realises a chord written as a Nashville number or Roman numeral in the given key. Returns
+/// `None` if the chord isn't written in either number system.
+pub fn from_notation(chord: &str, key: Key) -> Option<String> {
+    let note = |accidental: &str, degree: usize| {
+        let offset = match accidental {
+            "b" => -1,
+            "#" => 1,
+            _ => 0,
+        };
+        key.spell(PitchClass::from_u8(
+            (key.tonic.into_u8() as i32 + key.mode.scale()[degree] as i32 + offset).rem_euclid(12)
+                as u8,
+        ))
+    };
+
+    if let Some(caps) = RE_NUMBER_CHORD.captures(chord) {
+        let degree = |number: &str| number.parse::<usize>().unwrap() - 1;
+        let root = note(
+            caps.get(1).unwrap().as_str(),
+            degree(caps.get(2).unwrap().as_str()),
+        );
+        let quality = caps.get(3).unwrap().as_str().replacen('-', "m", 1);
+        let mut realised = format!("{}{}", root, quality);
+        if let Some(bass) = caps.get(5) {
+            let accidental = caps.get(4).map_or("", |m| m.as_str());
+            realised += &format!("/{}", note(accidental, degree(bass.as_str())));
+        }
+        return Some(realised);
+    }
+
+    if let Some(caps) = RE_ROMAN_CHORD.captures(chord) {
+        let degree = |numeral: &str| {
+            NUMERALS
+                .iter()
+                .position(|n| *n == numeral.to_uppercase())
+                .unwrap()
+        };
+        let numeral = caps.get(2).unwrap().as_str();
+        let root = note(caps.get(1).unwrap().as_str(), degree(numeral));
+        let quality = caps.get(3).unwrap().as_str();
+        let quality = match quality.strip_prefix('°') {
+            Some(rest) => format!("dim{}", rest),
+            None if numeral.chars().all(char::is_lowercase) => format!("m{}", quality),
+            None => quality.to_owned(),
+        };
+        let mut realised = format!("{}{}", root, quality);
+        if let Some(bass) = caps.get(5) {
+            let accidental = caps.get(4).map_or("", |m| m.as_str());
+            realised += &format!("/{}", note(accidental, degree(bass.as_str())));
+        }
+        return Some(realised);
+    }
+
+    None
+}
+
+/// Estimates the major or minor key of a chord progression by how many of its chords are
+/// diatonic to each key, giving extra weight to the tonic and to the first and last chord
+pub fn estimate_key(chords: &[&str]) -> Option<Key> {
+    let chords: Vec<(u8, Quality)> = chords
+        .iter()
+        .filter_map(|chord| {
+            let caps = RE_LETTER_CHORD.captures(chord)?;
+            let root = PitchClass::from_str(caps.get(1).unwrap().as_str())?;
+            Some((root.into_u8(), Quality::from(caps.get(2).unwrap().as_str())))
+        })
+        .collect();
+    let (first, last) = (chords.first()?, chords.last()?);
+
+    // Major keys come last so they win ties with their relative minor
+    [Mode::Minor, Mode::Major]
+        .iter()
+        .flat_map(|mode| (0..12u8).map(move |tonic| Key::new(PitchClass::from_u8(tonic), *mode)))
+        .max_by_key(|key| {
+            let scale = key.mode.scale();
+            let fit = |&(root, quality): &(u8, Quality)| {
+                let semitones = (root + 12 - key.tonic.into_u8()) % 12;
+                match scale.iter().position(|s| *s == semitones) {
+                    Some(degree) if Quality::diatonic(scale, degree) == quality => 2,
+                    Some(_) => 1,
+                    None => 0,
+                }
+            };
+            let tonic_weight = |&(root, quality): &(u8, Quality)| match root == key.tonic.into_u8()
+                && quality == Quality::diatonic(scale, 0)
+            {
+                true => 1,
+                false => 0,
+            };
+            chords
+                .iter()
+                .map(|chord| fit(chord) + tonic_weight(chord))
+                .sum::<i32>()
+                + 2 * (fit(first) + tonic_weight(first))
+                + 3 * (fit(last) + tonic_weight(last))
+        })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Quality {
+    Major,
+    Minor,
+    Diminished,
+}
+
+impl Quality {
+    fn from(quality: &str) -> Self {
+        if quality.starts_with("dim") {
+            Quality::Diminished
+        } else if quality.starts_with('m') && !quality.starts_with("maj") {
+            Quality::Minor
+        } else {
+            Quality::Major
+        }
+    }
+
+    /// Returns the quality of the triad built on a degree of a scale
+    fn diatonic(scale: [u8; 7], degree: usize) -> Self {
+        let third = (scale[(degree + 2) % 7] + 12 - scale[degree]) % 12;
+        let fifth = (scale[(degree + 4) % 7] + 12 - scale[degree]) % 12;
+        match (third, fifth) {
+            (4, _) => Quality::Major,
+            (3, 6) => Quality::Diminished,
+            _ => Quality::Minor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: &str) -> Key {
+        key.parse().unwrap()
+    }
+
+    #[test]
+    fn writes_chords_as_numbers() {
+        let nashville = |chord| to_notation(chord, key("G"), ChordNotation::Nashville);
+        assert_eq!(nashville("G"), "1");
+        assert_eq!(nashville("Em7"), "6m7");
+        assert_eq!(nashville("D/F#"), "5/7");
+        assert_eq!(nashville("F"), "b7");
+        assert_eq!(nashville("N.C."), "N.C.");
+        let roman = |chord| to_notation(chord, key("C"), ChordNotation::Roman);
+        assert_eq!(roman("Am"), "vi");
+        assert_eq!(roman("Bdim"), "vii°");
+        assert_eq!(roman("Bbmaj7"), "bVIImaj7");
+        assert_eq!(roman("G/B"), "V/VII");
+        assert_eq!(to_notation("Am", key("C"), ChordNotation::Letters), "Am");
+    }
+
+    #[test]
+    fn realises_numbers_in_a_key() {
+        assert_eq!(from_notation("1", key("G")).as_deref(), Some("G"));
+        assert_eq!(from_notation("5/7", key("G")).as_deref(), Some("D/F#"));
+        assert_eq!(from_notation("6-7", key("C")).as_deref(), Some("Am7"));
+        assert_eq!(from_notation("b7", key("C")).as_deref(), Some("Bb"));
+        assert_eq!(from_notation("vi", key("C")).as_deref(), Some("Am"));
+        assert_eq!(from_notation("vii°", key("C")).as_deref(), Some("Bdim"));
+        assert_eq!(from_notation("IV/V", key("D")).as_deref(), Some("G/A"));
+        assert_eq!(from_notation("Am", key("C")), None);
+    }
+
+    #[test]
+    fn estimates_keys_from_chords() {
+        assert_eq!(estimate_key(&["G", "C", "D", "G"]), Some(key("G")));
+        assert_eq!(
+            estimate_key(&["D", "A", "Bm", "G", "A", "D"]),
+            Some(key("D"))
+        );
+        assert_eq!(estimate_key(&["F", "Bb", "C7", "F"]), Some(key("F")));
+        assert_eq!(estimate_key(&["N.C."]), None);
+        assert_eq!(estimate_key(&[]), None);
+    }
+
+    #[test]
+    fn writes_chords_relative_to_minor_keys() {
+        let nashville = |chord| to_notation(chord, key("Am"), ChordNotation::Nashville);
+        assert_eq!(nashville("Am"), "1m");
+        assert_eq!(nashville("C"), "3");
+        assert_eq!(nashville("G"), "7");
+        assert_eq!(nashville("Dm7"), "4m7");
+        let roman = |chord| to_notation(chord, key("Em"), ChordNotation::Roman);
+        assert_eq!(roman("Em"), "i");
+        assert_eq!(roman("C"), "VI");
+        assert_eq!(roman("D"), "VII");
+        assert_eq!(from_notation("3", key("Am")).as_deref(), Some("C"));
+    }
+
+    #[test]
+    fn writes_every_note_of_every_mode_as_a_number() {
+        for mode in [Mode::Major, Mode::Minor, Mode::Dorian, Mode::Mixolydian] {
+            let key = Key::new(PitchClass::A, mode);
+            for semitones in 0..12 {
+                let note = PitchClass::from_u8(PitchClass::A.into_u8() + semitones);
+                let chord = key.spell(note);
+                for notation in [ChordNotation::Nashville, ChordNotation::Roman] {
+                    let written = to_notation(&chord, key, notation);
+                    let realised = from_notation(&written, key).unwrap();
+                    assert_eq!(
+                        PitchClass::from_str(&realised),
+                        Some(note),
+                        "{} as {} in {}",
+                        chord,
+                        written,
+                        key
+                    );
+                }
+            }
+        }
+        // The major seventh is a raised seventh in modes with a flat seventh
+        assert_eq!(
+            to_notation("G#dim", key("Am"), ChordNotation::Nashville),
+            "#7dim"
+        );
+        assert_eq!(
+            to_notation("G#dim", key("Am"), ChordNotation::Roman),
+            "#vii°"
+        );
+    }
+}
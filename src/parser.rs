@@ -1,11 +1,22 @@
 extern crate rust_music_theory as rustmt;
 
-use crate::{app::FileType, conf::Theme};
+use crate::{
+    conf::{ChordDisplay, ChordNotation, ChorusRecall, Config, Theme},
+    key::Key,
+    library::FileType,
+    notation,
+};
 use lazy_static::lazy_static;
 use regex::{Captures, Regex};
 use rustmt::{interval::Interval, note::PitchClass};
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use tui::text::{Span, Spans};
+use tui::{
+    style::Modifier,
+    text::{Span, Spans},
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 lazy_static! {
     static ref RE_NEWLINES: Regex = Regex::new(r"(\n\r?|\r\n?)").unwrap();
@@ -18,34 +29,93 @@ lazy_static! {
     static ref RE_SPACES: Regex = Regex::new(r" +").unwrap();
     static ref RE_BLOCKS: Regex = Regex::new(r"[^ \n]+ *").unwrap();
     static ref RE_SONG_TRANSPOSITION: Regex = Regex::new(r" \[([ABCDEFG][b#]?)\]").unwrap();
+    static ref RE_NEW_SONG: Regex =
+        Regex::new(r"(?m)^[ \t]*\{(?:new_song|ns)\}[ \t]*\r?$").unwrap();
 }
 
-#[derive(Debug, Clone)]
+/// The highest fret a capo can be put on
+pub const MAX_CAPO: i32 = 11;
+
+/// Directives with a hyphen in their name, which isn't followed by a selector
+const HYPHENATED_DIRECTIVES: [&str; 1] = ["Capo-Bass_Guitar"];
+
+/// The number of columns lines that continue a wrapped line are indented by
+const CONTINUATION_INDENT: usize = 2;
+
+/// Chords that can be played as open shapes in first position
+const OPEN_CHORDS: &[&str] = &[
+    "C", "Cmaj7", "C7", "D", "Dm", "D7", "Dm7", "Dsus2", "Dsus4", "E", "Em", "E7", "Em7", "Esus4",
+    "G", "G7", "A", "Am", "A7", "Am7", "Asus2", "Asus4", "Amaj7", "Fmaj7", "B7",
+];
+
+/// The number of terminal columns text takes up. Wide characters like those of Chinese,
+/// Japanese and Korean take up two columns and combining accents none, counted per grapheme like
+/// the terminal draws them.
+pub fn display_width(text: &str) -> usize {
+    text.graphemes(true).map(UnicodeWidthStr::width).sum()
+}
+
+/// A piece of a line: a chord, the lyrics after it, or a comment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SongString {
     Chord(String),
     Text(String),
     Comment(String),
+    /// A comment set in italics, from `{comment_italic}`
+    CommentItalic(String),
+    /// A comment drawn in a box, from `{comment_box}`
+    CommentBox(String),
+    /// A comment that stands out, from `{highlight}`
+    Highlight(String),
+    /// Text in the place of a chord that isn't a chord, like `[*Riff]`
+    Annotation(String),
+}
+
+impl SongString {
+    /// Whether the string is one of the kinds of comment
+    pub fn is_comment(&self) -> bool {
+        matches!(
+            self,
+            SongString::Comment(_)
+                | SongString::CommentItalic(_)
+                | SongString::CommentBox(_)
+                | SongString::Highlight(_)
+        )
+    }
+
+    /// The text of the string, whatever kind it is
+    pub fn text(&self) -> &str {
+        match self {
+            SongString::Chord(text)
+            | SongString::Text(text)
+            | SongString::Comment(text)
+            | SongString::CommentItalic(text)
+            | SongString::CommentBox(text)
+            | SongString::Highlight(text)
+            | SongString::Annotation(text) => text,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct SongBlock(Vec<SongString>);
+/// A word of a line along with the chords above it, which is kept together when lines wrap
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SongBlock(pub Vec<SongString>);
 
 impl SongBlock {
-    pub fn from(input: &str, transposition: i32) -> Self {
+    /// Parses a word with chords in brackets, transposing the chords by the given number of
+    /// semitones
+    pub fn from(input: &str, transposition: i32, key: Option<Key>) -> Self {
         SongBlock(
             Song::regex_split_keep(&RE_CHORDS, input)
                 .iter()
-                .map(|part| match RE_CHORDS.captures(&part) {
+                .map(|part| match RE_CHORDS.captures(part) {
                     Some(chord) => {
                         let chord = chord.get(1).unwrap().as_str();
-                        let transposed = RE_ROOT_NOTE.replace_all(chord, |caps: &Captures| {
-                            PitchClass::from_interval(
-                                PitchClass::from_str(caps.get(0).unwrap().as_str()).unwrap(),
-                                Interval::from_semitone(((transposition + 12) % 12) as u8).unwrap(),
-                            )
-                            .to_string()
-                        });
-                        SongString::Chord(transposed.to_string())
+                        if let Some(annotation) = chord.strip_prefix('*') {
+                            return SongString::Annotation(annotation.to_owned());
+                        }
+                        SongString::Chord(Song::transpose_chord(chord, transposition, key))
                     }
                     None => SongString::Text(part.to_string()),
                 })
@@ -53,56 +123,200 @@ impl SongBlock {
         )
     }
 
-    pub fn from_comment(c: &str) -> Self {
-        SongBlock(vec![SongString::Comment(c.to_owned())])
-    }
-
+    /// The number of columns the block takes up, whichever is longer of the chords and the text
     pub fn width(&self) -> usize {
         let mut chords: usize = 0;
         let mut text: usize = 0;
         self.0.iter().for_each(|songstring| match songstring {
-            SongString::Chord(c) => {
+            SongString::Chord(c) | SongString::Annotation(c) => {
                 match text.cmp(&chords) {
                     Ordering::Less => text = chords,
                     Ordering::Greater => chords = text,
                     Ordering::Equal => (),
                 }
-                chords += c.chars().count() + 1;
+                chords += display_width(c) + 1;
             }
-            SongString::Text(t) => {
-                text += t.chars().count();
+            // Boxes are drawn as brackets around the comment
+            SongString::CommentBox(c) => {
+                text += display_width(c) + 2;
             }
-            SongString::Comment(c) => {
-                text += c.chars().count();
+            songstring => {
+                text += display_width(songstring.text());
             }
         });
         std::cmp::max(chords, text)
     }
+
+    /// Whether the block has lyrics or a comment, rather than only chords over spaces
+    fn has_text(&self) -> bool {
+        self.0.iter().any(|songstring| match songstring {
+            SongString::Chord(_) | SongString::Annotation(_) => false,
+            songstring => !songstring.text().trim().is_empty(),
+        })
+    }
+
+    /// Splits a word that is wider than the available width before its chords, so every piece
+    /// keeps its chord above its syllable. Pieces that break a word end in a hyphen.
+    fn split(&self, available: usize) -> Vec<SongBlock> {
+        // Syllables along with the chord above them
+        let mut syllables: Vec<Vec<SongString>> = vec![];
+        for songstring in self.0.iter() {
+            match (songstring, syllables.last_mut()) {
+                (SongString::Chord(_) | SongString::Annotation(_), _) | (_, None) => {
+                    syllables.push(vec![songstring.clone()])
+                }
+                (_, Some(syllable)) => syllable.push(songstring.clone()),
+            }
+        }
+
+        let mut pieces: Vec<SongBlock> = vec![];
+        let mut piece: Vec<SongString> = vec![];
+        for syllable in syllables {
+            let candidate = SongBlock([piece.as_slice(), syllable.as_slice()].concat());
+            if !piece.is_empty() && candidate.width() > available {
+                pieces.push(SongBlock(std::mem::take(&mut piece)));
+            }
+            piece.extend(syllable);
+        }
+        pieces.push(SongBlock(piece));
+
+        let last = pieces.len() - 1;
+        for piece in pieces[..last].iter_mut() {
+            if let Some(SongString::Text(text)) = piece.0.last_mut() {
+                if text.chars().last().is_some_and(char::is_alphanumeric) {
+                    text.push('-');
+                }
+            }
+        }
+        pieces
+    }
 }
 
-#[derive(Debug, Default, Clone)]
+/// A break the song asks for with `{column_break}` or `{new_page}`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Break {
+    Column,
+    Page,
+}
+
+/// The instrument and user songs are read for, which selectors like `guitar` in
+/// `{comment-guitar: ...}` are matched against. The default is no instrument or user, so only
+/// negated selectors match.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Selection {
+    pub instrument: Option<String>,
+    pub user: Option<String>,
+}
+
+impl Selection {
+    /// Whether a selector like `guitar` matches the instrument or user. Selectors are negated
+    /// with `!` before or after them, like `!guitar` or `guitar!`.
+    pub fn matches(&self, selector: &str) -> bool {
+        let (negated, selector) = match (selector.strip_prefix('!'), selector.strip_suffix('!')) {
+            (Some(selector), _) | (_, Some(selector)) => (true, selector.trim()),
+            _ => (false, selector.trim()),
+        };
+        let matches = [&self.instrument, &self.user]
+            .iter()
+            .filter_map(|name| name.as_deref())
+            .any(|name| name.eq_ignore_ascii_case(selector));
+        matches != negated
+    }
+}
+
+/// The part of a section repeated with `{chorus}` a line is
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Recalled {
+    /// The label shown instead of the section
+    Label,
+    /// The first line with lyrics or chords
+    First,
+    /// The rest of the section
+    Rest,
+}
+
+/// A line of a song, which is shown as a line of chords above a line of lyrics
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct SongLine {
-    blocks: Vec<SongBlock>,
-    chorus: bool,
+    pub blocks: Vec<SongBlock>,
+    pub chorus: bool,
+    /// Whether the line continues the line before it, because it was too long to fit
+    #[serde(default)]
+    pub continued: bool,
+    /// A column or page break before the line
+    #[serde(default)]
+    pub break_before: Option<Break>,
+    /// Which part of a section repeated with `{chorus}` the line is
+    #[serde(default)]
+    pub recalled: Option<Recalled>,
+    /// The instruments or users the line is shown for, like `guitar` or `!keys` for everyone
+    /// but keyboardists. All of them have to match.
+    #[serde(default)]
+    pub selectors: Vec<String>,
 }
 
 impl SongLine {
     pub fn from(blocks: Vec<SongBlock>, chorus: bool) -> Self {
-        SongLine { blocks, chorus }
+        SongLine {
+            blocks,
+            chorus,
+            continued: false,
+            break_before: None,
+            recalled: None,
+            selectors: vec![],
+        }
+    }
+
+    /// Whether the line is shown for the instrument and user of a selection
+    pub fn is_selected(&self, selection: &Selection) -> bool {
+        self.selectors
+            .iter()
+            .all(|selector| selection.matches(selector))
+    }
+
+    /// Whether the line has lyrics, rather than only chords or a comment
+    fn has_lyrics(&self) -> bool {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.0.iter())
+            .any(|string| matches!(string, SongString::Text(text) if !text.trim().is_empty()))
     }
 
+    /// The number of columns the line takes up when it is shown
     pub fn width(&self) -> usize {
         self.format(&Theme::default())
             .iter()
-            .map(|s| s.width())
+            .map(|spans| {
+                spans
+                    .0
+                    .iter()
+                    .map(|span| display_width(&span.content))
+                    .sum()
+            })
             .max()
             .unwrap_or(0)
     }
 
+    /// Returns the chords of the line in order
+    pub fn chords(&self) -> Vec<&str> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.0.iter())
+            .filter_map(|string| match string {
+                SongString::Chord(chord) => Some(chord.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The number of rows the line takes up when it is shown
     pub fn height(&self) -> usize {
         self.format(&Theme::default()).len()
     }
 
+    /// Styles the line for the terminal, as a row of chords above a row of lyrics
     pub fn format<'a>(&self, theme: &Theme) -> Vec<Spans<'a>> {
         let mut has_chords = false;
         let mut chords: Vec<Span<'a>> = vec![];
@@ -111,12 +325,18 @@ impl SongLine {
             chords.push(Span::styled("| ", theme.comment.to_style()));
             text.push(Span::styled("| ", theme.comment.to_style()));
         }
+        if self.continued {
+            chords.push(Span::from(" ".repeat(CONTINUATION_INDENT)));
+            text.push(Span::from(" ".repeat(CONTINUATION_INDENT)));
+        }
         self.blocks.iter().for_each(|block| {
             block.0.iter().for_each(|songstring| match songstring {
-                SongString::Chord(c) => {
+                SongString::Chord(c) | SongString::Annotation(c) => {
                     has_chords = true;
-                    let text_len: usize = text.iter().map(Span::width).sum();
-                    let chords_len: usize = chords.iter().map(Span::width).sum();
+                    let text_len: usize =
+                        text.iter().map(|span| display_width(&span.content)).sum();
+                    let chords_len: usize =
+                        chords.iter().map(|span| display_width(&span.content)).sum();
                     match text_len.cmp(&chords_len) {
                         Ordering::Equal => (),
                         Ordering::Less => {
@@ -133,7 +353,11 @@ impl SongLine {
                             chords.push(Span::from(" ".repeat(text_len - chords_len)))
                         }
                     }
-                    chords.push(Span::styled(c.to_owned() + " ", theme.chord.to_style()));
+                    let style = match songstring {
+                        SongString::Annotation(_) => theme.annotation.to_style(),
+                        _ => theme.chord.to_style(),
+                    };
+                    chords.push(Span::styled(c.to_owned() + " ", style));
                 }
                 SongString::Text(t) => {
                     text.push(Span::styled(t.to_owned(), theme.lyrics.to_style()));
@@ -141,6 +365,19 @@ impl SongLine {
                 SongString::Comment(c) => {
                     text.push(Span::styled(c.to_owned(), theme.comment.to_style()));
                 }
+                SongString::CommentItalic(c) => {
+                    text.push(Span::styled(c.to_owned(), theme.comment_italic.to_style()));
+                }
+                SongString::CommentBox(c) => {
+                    text.push(Span::styled(
+                        format!("[{}]", c),
+                        theme.comment_box.to_style(),
+                    ));
+                }
+                SongString::Highlight(c) => text.push(Span::styled(
+                    c.to_owned(),
+                    theme.comment.to_style().add_modifier(Modifier::REVERSED),
+                )),
             })
         });
         let mut formatted = vec![];
@@ -151,6 +388,9 @@ impl SongLine {
         formatted
     }
 
+    /// Splits the line into lines that fit in the given number of columns. Lines break between
+    /// words, and words too wide for a line break before one of their chords, so chords always
+    /// stay above their syllable. The lines after the first are indented.
     pub fn wrap(&self, max_width: usize) -> Vec<Self> {
         if max_width >= self.width() {
             return vec![self.clone()];
@@ -159,93 +399,256 @@ impl SongLine {
             true => 2,
             false => 0,
         };
+        let available = max_width.saturating_sub(chorus_width + CONTINUATION_INDENT);
 
         let mut total_width = 0;
-        let mut wrapped_line = vec![];
+        let mut wrapped_line: Vec<SongBlock> = vec![];
         let mut wrapped_lines = vec![];
+        for unit in self.units(available) {
+            let indent = match wrapped_lines.is_empty() {
+                true => 0,
+                false => CONTINUATION_INDENT,
+            };
+            let unit_width: usize = unit.iter().map(SongBlock::width).sum();
+            if !wrapped_line.is_empty()
+                && chorus_width + indent + total_width + unit_width > max_width
+            {
+                wrapped_lines.push(SongLine {
+                    blocks: std::mem::take(&mut wrapped_line),
+                    continued: indent > 0,
+                    break_before: self.break_before.filter(|_| indent == 0),
+                    ..self.clone()
+                });
+                total_width = 0;
+            }
+            total_width += unit_width;
+            wrapped_line.extend(unit);
+        }
+        wrapped_lines.push(SongLine {
+            blocks: wrapped_line,
+            continued: !wrapped_lines.is_empty(),
+            break_before: self.break_before.filter(|_| wrapped_lines.is_empty()),
+            ..self.clone()
+        });
+        wrapped_lines
+    }
 
+    /// Groups the blocks of the line into the pieces it may break between. Chords over nothing
+    /// but spaces are kept with the word after them, or before them at the end of the line.
+    /// Words wider than the available width are split before their chords.
+    fn units(&self, available: usize) -> Vec<Vec<SongBlock>> {
+        let mut units: Vec<Vec<SongBlock>> = vec![];
+        let mut pending: Vec<SongBlock> = vec![];
         for block in self.blocks.iter() {
-            let block_width = block.width();
-
-            if total_width + block_width + chorus_width < max_width {
-                wrapped_line.push(block.clone());
-                total_width += block.width();
-            } else {
-                wrapped_lines.push(SongLine::from(wrapped_line, self.chorus));
-                wrapped_line = vec![block.clone()];
-                total_width = block.width();
+            pending.push(block.clone());
+            if block.has_text() {
+                units.push(std::mem::take(&mut pending));
             }
         }
-        wrapped_lines.push(SongLine::from(wrapped_line, self.chorus));
+        match units.last_mut() {
+            Some(last) => last.append(&mut pending),
+            None if !pending.is_empty() => units.push(pending),
+            None => (),
+        }
 
-        wrapped_lines
+        units
+            .into_iter()
+            .flat_map(|unit| {
+                if unit.iter().map(SongBlock::width).sum::<usize>() <= available {
+                    return vec![unit];
+                }
+                let word = SongBlock(unit.into_iter().flat_map(|block| block.0).collect());
+                word.split(available)
+                    .into_iter()
+                    .map(|piece| vec![piece])
+                    .collect()
+            })
+            .collect()
     }
 }
 
-#[derive(Debug, Default, Clone)]
+/// A song parsed from ChordPro
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Song {
     pub title: String,
     pub subtitle: String,
+    /// Semitones the chords were transposed by while parsing
     pub transposition: i32,
-    pub key: Option<PitchClass>,
+    /// The capo fret the song asks for
+    pub capo: i32,
+    pub key: Option<Key>,
+    /// The key the song is written in, before any transposition
+    pub original_key: Option<Key>,
+    /// Whether the key was estimated from the chords because the song has no key tag
+    pub key_estimated: bool,
+    /// Beats per minute
+    pub tempo: Option<u32>,
     pub content: Vec<SongLine>,
 }
 
 impl Song {
+    /// Parses a song from ChordPro for no instrument or user, unlike [`Config::default`] which
+    /// reads songs for a guitar. Directives with a selector, like `{comment-guitar: ...}`, are
+    /// left out unless negated; use [`Song::parse`] with [`Config::selection`] to keep them.
+    ///
+    /// [`Config::default`]: crate::conf::Config
+    /// [`Config::selection`]: crate::conf::Config::selection
     pub fn from(songstring: String) -> Self {
-        Song::new(songstring, None)
+        Song::parse(songstring, None, &Selection::default())
     }
 
-    pub fn in_key(songstring: String, key: PitchClass) -> Self {
-        Song::new(songstring, Some(key))
+    /// Parses a song from ChordPro, transposing it into the given key. Like [`Song::from`], it
+    /// reads the song for no instrument or user.
+    pub fn in_key(songstring: String, key: Key) -> Self {
+        Song::parse(songstring, Some(key), &Selection::default())
     }
 
+    /// Transposes the song by a number of semitones
     pub fn change_key(&mut self, transposition: i32) {
-        let old_key = self.key.unwrap();
-        let interval = Interval::from_semitone(((transposition + 12) % 12) as u8).unwrap();
-        self.content = self
-            .content
+        self.key = self.key.map(|key| key.transpose(transposition));
+        self.content =
+            self.map_chords(|chord| Song::transpose_chord(chord, transposition, self.key));
+    }
+
+    /// Transposes the song into the given key. When the song is in a different mode, it moves to
+    /// the key with the same key signature. Songs without a key are left as they are.
+    pub fn set_key(&mut self, key: Key) {
+        if let Some(transposition) = self.transposition_to(key) {
+            self.change_key(transposition);
+        }
+    }
+
+    /// Returns the semitones between the key of the song and the given key, or `None` if the
+    /// song has no key
+    fn transposition_to(&self, key: Key) -> Option<i32> {
+        let current = self.key?;
+        let (from, to) = match key.mode == current.mode {
+            true => (current.tonic, key.tonic),
+            false => (current.relative_major(), key.relative_major()),
+        };
+        Some(to.into_u8() as i32 - from.into_u8() as i32)
+    }
+
+    /// Returns the song content with its chords written the way the user wants to read them,
+    /// leaving out the lines meant for other instruments and users
+    pub fn display(&self, capo: i32, config: &Config) -> Vec<SongLine> {
+        let mut content = match config.chord_notation {
+            ChordNotation::Letters => None,
+            notation => self.with_notation(notation),
+        }
+        .unwrap_or_else(|| self.with_capo(capo, config.chord_display));
+        let selection = config.selection();
+        content.retain(|line| line.is_selected(&selection));
+        content.retain(|line| match (line.recalled, config.chorus_recall) {
+            (None, _) => true,
+            (Some(recalled), ChorusRecall::Full) => recalled != Recalled::Label,
+            (Some(recalled), ChorusRecall::Label) => recalled == Recalled::Label,
+            (Some(recalled), ChorusRecall::FirstLine) => recalled != Recalled::Rest,
+        });
+        content
+    }
+
+    /// Returns the song content with its chords displayed for a capo on the given fret
+    pub fn with_capo(&self, capo: i32, display: ChordDisplay) -> Vec<SongLine> {
+        if capo == 0 {
+            return self.content.clone();
+        }
+        let shape_key = self.key.map(|key| key.transpose(-capo));
+        let shape = |chord: &str| Song::transpose_chord(chord, -capo, shape_key);
+        match display {
+            ChordDisplay::Concert => self.content.clone(),
+            ChordDisplay::Shapes => self.map_chords(shape),
+            ChordDisplay::Both => self.map_chords(|chord| format!("{}({})", chord, shape(chord))),
+        }
+    }
+
+    /// Returns the song content with its chords written relative to the key of the song, or
+    /// `None` if the song has no key
+    pub fn with_notation(&self, notation: ChordNotation) -> Option<Vec<SongLine>> {
+        let key = self.key?;
+        Some(self.map_chords(|chord| notation::to_notation(chord, key, notation)))
+    }
+
+    /// Finds the capo fret for which the most chords of the song can be played as open shapes
+    /// when it sounds in the target key, or in its own key without a target. Returns the fret and
+    /// the key of the shapes, preferring the lowest fret when several positions are equally easy.
+    /// Songs without chords, or without a key to move to the target from, have no suggestion.
+    pub fn suggest_capo(&self, target: Option<Key>) -> Option<(i32, Option<Key>)> {
+        let transposition = match target {
+            Some(target) => self.transposition_to(target)?,
+            None => 0,
+        };
+        let chords = self.chords();
+        if chords.is_empty() {
+            return None;
+        }
+        let capo = (0..=MAX_CAPO).rev().max_by_key(|capo| {
+            chords
+                .iter()
+                .filter(|chord| {
+                    let shape = Song::transpose_chord(chord, transposition - capo, None);
+                    OPEN_CHORDS.contains(&shape.as_str())
+                })
+                .count()
+        })?;
+        let key = target.or(self.key);
+        Some((capo, key.map(|key| key.transpose(-capo))))
+    }
+
+    /// Returns every chord of the song in order
+    pub fn chords(&self) -> Vec<&str> {
+        self.content.iter().flat_map(SongLine::chords).collect()
+    }
+
+    fn map_chords<F>(&self, f: F) -> Vec<SongLine>
+    where
+        F: Fn(&str) -> String,
+    {
+        self.content
             .iter()
-            .map(|line| {
-                SongLine::from(
-                    line.blocks
-                        .iter()
-                        .map(|block| {
-                            SongBlock(
-                                block
-                                    .0
-                                    .iter()
-                                    .map(|string| match string {
-                                        SongString::Chord(chord) => {
-                                            let transposed = RE_ROOT_NOTE.replace_all(
-                                                &chord,
-                                                |caps: &Captures| {
-                                                    PitchClass::from_interval(
-                                                        PitchClass::from_str(
-                                                            caps.get(0).unwrap().as_str(),
-                                                        )
-                                                        .unwrap(),
-                                                        interval,
-                                                    )
-                                                    .to_string()
-                                                },
-                                            );
-                                            SongString::Chord(transposed.to_string())
-                                        }
-                                        s => s.clone(), //TODO: remove .clone() call?
-                                    })
-                                    .collect(),
-                            )
-                        })
-                        .collect(),
-                    line.chorus,
-                )
+            .map(|line| SongLine {
+                blocks: line
+                    .blocks
+                    .iter()
+                    .map(|block| {
+                        SongBlock(
+                            block
+                                .0
+                                .iter()
+                                .map(|string| match string {
+                                    SongString::Chord(chord) => SongString::Chord(f(chord)),
+                                    s => s.clone(),
+                                })
+                                .collect(),
+                        )
+                    })
+                    .collect(),
+                ..line.clone()
             })
-            .collect();
-        self.key = Some(PitchClass::from_interval(old_key, interval));
+            .collect()
+    }
+
+    /// Transposes a chord, spelling its notes for the given key or with sharps if there is none
+    fn transpose_chord(chord: &str, transposition: i32, key: Option<Key>) -> String {
+        let interval = Interval::from_semitone(transposition.rem_euclid(12) as u8).unwrap();
+        RE_ROOT_NOTE
+            .replace_all(chord, |caps: &Captures| {
+                let note = PitchClass::from_interval(
+                    PitchClass::from_str(caps.get(0).unwrap().as_str()).unwrap(),
+                    interval,
+                );
+                match key {
+                    Some(key) => key.spell(note),
+                    None => note.to_string(),
+                }
+            })
+            .to_string()
     }
 
-    fn new(songstring: String, key: Option<PitchClass>) -> Self {
+    /// Parses a song from ChordPro for the instrument and user of a selection, transposing it
+    /// into the given key. Directives like `{capo-keys: 5}` only count when their selector
+    /// matches, while lines keep their selectors so they can be left out when they are shown.
+    pub fn parse(songstring: String, key: Option<Key>, selection: &Selection) -> Self {
         let songstring = RE_NEWLINES.replace_all(&songstring, "\n");
         let songstring = RE_SPACES.replace_all(&songstring, " ");
 
@@ -256,14 +659,37 @@ impl Song {
 
         let mut chorus = false;
         let mut comment = false;
+        let mut pending_break = None;
+        // Sections that can be recalled with {chorus}, with their label and whether they are a
+        // chorus, and the section that is being read
+        let mut sections: Vec<(Option<&str>, bool, Vec<SongLine>)> = vec![];
+        let mut section_start = None;
+        // The selector of the section that is being read, like `keys` in `{start_of_chorus-keys}`
+        let mut section_selector = None;
         for line in songstring.lines() {
             let mut tag = false;
             let mut blocks: Vec<SongBlock> = vec![];
-            for section in Song::regex_split_keep(&RE_TAGS, &line) {
-                match RE_TAGS.captures(&section) {
+            let mut line_selector = None;
+            for section in Song::regex_split_keep(&RE_TAGS, line) {
+                match RE_TAGS.captures(section) {
                     Some(cap) => {
                         tag = true;
-                        match cap.get(1).unwrap().as_str() {
+                        let directive = cap.get(1).unwrap().as_str();
+                        let (name, selector) = match directive.split_once('-') {
+                            Some((name, selector))
+                                if !HYPHENATED_DIRECTIVES.contains(&directive) =>
+                            {
+                                (name, Some(selector.trim()))
+                            }
+                            _ => (directive, None),
+                        };
+                        // Directives that aren't content only count for the selection
+                        if selector.is_some_and(|selector| {
+                            !Song::is_content(name) && !selection.matches(selector)
+                        }) {
+                            continue;
+                        }
+                        match name {
                             "t" | "title" => {
                                 song.title = String::from(cap.get(2).unwrap().as_str().trim());
                             }
@@ -271,66 +697,243 @@ impl Song {
                                 song.subtitle = String::from(cap.get(2).unwrap().as_str().trim());
                             }
                             "key" => {
-                                let original_key =
-                                    PitchClass::from_str(cap.get(2).unwrap().as_str().trim());
-                                match song.key {
-                                    Some(display_key) => {
-                                        song.transposition += display_key.into_u8() as i32
-                                            - original_key
-                                                .expect("Error parsing key tag in song")
-                                                .into_u8()
+                                // Keys that can't be parsed are left for estimation instead
+                                if let Ok(original_key) =
+                                    cap.get(2).unwrap().as_str().parse::<Key>()
+                                {
+                                    song.original_key = Some(original_key);
+                                    // Playlists may ask for the relative key of a song
+                                    let display_key = song.key.map(|key| match key.mode {
+                                        mode if mode == original_key.mode => key,
+                                        _ => key.relative(),
+                                    });
+                                    match display_key {
+                                        Some(display_key) => {
+                                            song.transposition += display_key.tonic.into_u8()
                                                 as i32
+                                                - original_key.tonic.into_u8() as i32;
+                                            song.key = Some(Key::new(
+                                                display_key.tonic,
+                                                original_key.mode,
+                                            ));
+                                        }
+                                        None => song.key = Some(original_key),
                                     }
-                                    None => song.key = original_key,
                                 }
                             }
                             "Capo-Bass_Guitar" => {
                                 let diff =
                                     cap.get(2).unwrap().as_str().trim().parse::<i32>().unwrap();
                                 song.transposition -= diff;
-                                if let Some(key) = song.key {
-                                    song.key = Some(PitchClass::from_u8(
-                                        (key.into_u8() as i32 - diff + 12) as u8 % 12,
-                                    ))
-                                }
+                                song.key = song.key.map(|key| key.transpose(-diff));
+                            }
+                            "capo" => {
+                                song.capo = cap
+                                    .get(2)
+                                    .unwrap()
+                                    .as_str()
+                                    .trim()
+                                    .parse::<i32>()
+                                    .unwrap_or(0)
+                                    .clamp(0, MAX_CAPO);
                             }
-                            "c" => blocks
-                                .append(&mut Song::parse_comment(cap.get(2).unwrap().as_str())),
-                            "soc" | "start_of_chorus" => {
-                                chorus = true;
+                            "c" | "comment" | "ci" | "comment_italic" | "highlight" => {
+                                line_selector = selector;
+                                let kind = match name {
+                                    "ci" | "comment_italic" => SongString::CommentItalic,
+                                    "highlight" => SongString::Highlight,
+                                    _ => SongString::Comment,
+                                };
+                                let comment = cap.get(2).map_or("", |comment| comment.as_str());
+                                blocks.append(&mut Song::parse_comment(comment, kind));
                             }
-                            "eoc" | "end_of_chorus" => {
+                            // Boxes are kept in one piece, as they can't be drawn across lines
+                            "cb" | "comment_box" => {
+                                line_selector = selector;
+                                let comment = cap.get(2).map_or("", |comment| comment.as_str());
+                                blocks.push(SongBlock(vec![SongString::CommentBox(
+                                    comment.trim().to_owned(),
+                                )]));
+                            }
+                            "soc" | "start_of_chorus" | "sov" | "start_of_verse" | "sob"
+                            | "start_of_bridge" => {
+                                chorus = matches!(name, "soc" | "start_of_chorus");
+                                section_selector = selector;
+                                let label = cap.get(2).map(|label| label.as_str().trim());
+                                if let Some(label) = label {
+                                    blocks.append(&mut Song::parse_comment(
+                                        label,
+                                        SongString::Comment,
+                                    ));
+                                }
+                                section_start = Some((song.content.len(), label, chorus));
+                            }
+                            "eoc" | "end_of_chorus" | "eov" | "end_of_verse" | "eob"
+                            | "end_of_bridge" => {
                                 chorus = false;
+                                section_selector = None;
+                                if let Some((start, label, chorus)) = section_start.take() {
+                                    sections.push((label, chorus, song.content[start..].to_vec()));
+                                }
+                            }
+                            "chorus" => {
+                                let label = cap.get(2).map(|label| label.as_str().trim());
+                                // Named sections are recalled by their label, and choruses
+                                // without a name are the last chorus
+                                let section = label
+                                    .and_then(|label| {
+                                        sections.iter().rev().find(|(name, _, _)| {
+                                            name.is_some_and(|name| {
+                                                name.eq_ignore_ascii_case(label)
+                                            })
+                                        })
+                                    })
+                                    .or_else(|| {
+                                        sections.iter().rev().find(|(_, chorus, _)| *chorus)
+                                    });
+                                let (chorus, lines) = section
+                                    .map_or((true, &[][..]), |(_, chorus, lines)| (*chorus, lines));
+                                let recalled =
+                                    Song::recall(label.unwrap_or("Chorus"), chorus, lines);
+                                song.content.extend(recalled.into_iter().map(|mut line| {
+                                    line.selectors.extend(selector.map(str::to_owned));
+                                    line
+                                }));
+                            }
+                            "column_break" | "colb" => pending_break = Some(Break::Column),
+                            "new_page" | "np" => pending_break = Some(Break::Page),
+                            "tempo" => {
+                                song.tempo = cap.get(2).unwrap().as_str().trim().parse().ok();
+                            }
+                            // Tab is shown as it is written
+                            "soh" | "sot" | "start_of_tab" => {
+                                comment = true;
+                                section_selector = selector;
+                            }
+                            "eoh" | "eot" | "end_of_tab" => {
+                                comment = false;
+                                section_selector = None;
                             }
-                            "soh" => comment = true,
-                            "eoh" => comment = false,
                             _ => (),
                         }
                     }
                     None => match comment {
-                        true => blocks.append(&mut Song::parse_comment(section)),
-                        false => blocks.append(&mut Song::parse_line(section, song.transposition)),
+                        true => {
+                            blocks.append(&mut Song::parse_comment(section, SongString::Comment))
+                        }
+                        false => blocks.append(&mut Song::parse_line(
+                            section,
+                            song.transposition,
+                            song.key,
+                        )),
                     },
                 }
             }
             if !blocks.is_empty() || !tag {
-                song.content.push(SongLine::from(blocks, chorus));
+                // Breaks go before the next line with something on it
+                let break_before = match blocks.is_empty() {
+                    true => None,
+                    false => pending_break.take(),
+                };
+                song.content.push(SongLine {
+                    break_before,
+                    selectors: section_selector
+                        .iter()
+                        .chain(line_selector.iter())
+                        .map(|selector| selector.to_string())
+                        .collect(),
+                    ..SongLine::from(blocks, chorus)
+                });
             }
         }
+        if song.key.is_none() {
+            song.key = notation::estimate_key(&song.chords());
+            song.original_key = song.key;
+            song.key_estimated = song.key.is_some();
+            // Spell the chords for the estimated key, as it wasn't known while parsing
+            song.content = song.map_chords(|chord| Song::transpose_chord(chord, 0, song.key));
+        }
+        // Chords written as numbers are realised once the key is known, wherever the key tag is
+        if let Some(key) = song.key {
+            song.content = song.map_chords(|chord| {
+                notation::from_notation(chord, key).unwrap_or_else(|| chord.to_owned())
+            });
+        }
         song
     }
 
-    fn parse_comment(input: &str) -> Vec<SongBlock> {
+    /// Whether a directive is content, which keeps its selector so it can be left out when the
+    /// song is shown. The ends of sections always end them, whatever their selector.
+    fn is_content(name: &str) -> bool {
+        matches!(
+            name,
+            "c" | "comment"
+                | "ci"
+                | "comment_italic"
+                | "cb"
+                | "comment_box"
+                | "highlight"
+                | "chorus"
+                | "soc"
+                | "start_of_chorus"
+                | "sov"
+                | "start_of_verse"
+                | "sob"
+                | "start_of_bridge"
+                | "soh"
+                | "sot"
+                | "start_of_tab"
+                | "eoc"
+                | "end_of_chorus"
+                | "eov"
+                | "end_of_verse"
+                | "eob"
+                | "end_of_bridge"
+                | "eoh"
+                | "eot"
+                | "end_of_tab"
+        )
+    }
+
+    /// Repeats a section for `{chorus}`. The lines are marked so the label, the first line or
+    /// the whole section can be shown depending on the config. A label is shown by itself if
+    /// there is nothing to repeat.
+    fn recall(label: &str, chorus: bool, lines: &[SongLine]) -> Vec<SongLine> {
+        let label = SongLine::from(Song::parse_comment(label, SongString::Comment), chorus);
+        if lines.is_empty() {
+            return vec![label];
+        }
+        let first = lines
+            .iter()
+            .position(|line| !line.chords().is_empty() || line.has_lyrics())
+            .unwrap_or(0);
+        let mut recalled = vec![SongLine {
+            recalled: Some(Recalled::Label),
+            ..label
+        }];
+        recalled.extend(lines.iter().enumerate().map(|(i, line)| SongLine {
+            recalled: Some(match i == first {
+                true => Recalled::First,
+                false => Recalled::Rest,
+            }),
+            break_before: None,
+            ..line.clone()
+        }));
+        recalled
+    }
+
+    /// Splits a comment into words of the given kind of comment
+    fn parse_comment(input: &str, kind: fn(String) -> SongString) -> Vec<SongBlock> {
         RE_BLOCKS
             .captures_iter(input)
-            .map(|cap| SongBlock::from_comment(cap.get(0).unwrap().as_str()))
+            .map(|cap| SongBlock(vec![kind(cap.get(0).unwrap().as_str().to_owned())]))
             .collect()
     }
 
-    fn parse_line(input: &str, transposition: i32) -> Vec<SongBlock> {
+    fn parse_line(input: &str, transposition: i32, key: Option<Key>) -> Vec<SongBlock> {
         RE_BLOCKS
             .captures_iter(input)
-            .map(|cap| SongBlock::from(cap.get(0).unwrap().as_str(), transposition))
+            .map(|cap| SongBlock::from(cap.get(0).unwrap().as_str(), transposition, key))
             .collect()
     }
 
@@ -350,27 +953,44 @@ impl Song {
         result
     }
 
+    /// Splits a file with several songs separated by `{new_song}` or `{ns}` into its songs
+    pub fn split(songstring: &str) -> Vec<String> {
+        let songs: Vec<String> = RE_NEW_SONG
+            .split(songstring)
+            .filter(|song| !song.trim().is_empty())
+            .map(|song| song.trim_start_matches(['\r', '\n']).to_owned())
+            .collect();
+        match songs.is_empty() {
+            true => vec![songstring.to_owned()],
+            false => songs,
+        }
+    }
+
+    /// Finds the name of a song in ChordPro, which is its title and subtitle. This is the name
+    /// the library and playlists know the song by.
     pub fn get_name(songstring: &str) -> Option<String> {
         let songstring = RE_SPACES.replace_all(songstring, " ");
-        let title = match RE_TITLE.captures(&songstring) {
-            Some(cap) => Some(cap.get(1).unwrap().as_str().trim().to_owned()),
-            None => None,
-        };
-        let subtitle = match RE_SUBTITLE.captures(&songstring) {
-            Some(cap) => Some(cap.get(1).unwrap().as_str().trim()),
-            None => None,
-        };
+        let title = RE_TITLE
+            .captures(&songstring)
+            .map(|cap| cap.get(1).unwrap().as_str().trim().to_owned());
+        let subtitle = RE_SUBTITLE
+            .captures(&songstring)
+            .map(|cap| cap.get(1).unwrap().as_str().trim());
 
         let title = match (title, subtitle) {
             (Some(t), Some(st)) => format!("{} - {}", t, st),
-            (Some(t), None) => format!("{}", t),
-            (None, Some(st)) => format!("{}", st),
+            (Some(t), None) => t.to_string(),
+            (None, Some(st)) => st.to_string(),
             (None, None) => return None,
         };
         Some(RE_SPACES.replace_all(&title, " ").to_string())
     }
 }
 
+/// A list of songs, read from a file with the title on the first line and the name of a song on
+/// every other line. Names may end in a key between brackets, like `Amazing Grace [A]`, and in
+/// a capo, like `Amazing Grace [A] {capo: 2}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Playlist {
     pub title: String,
     pub songs: Vec<FileType>,
@@ -378,7 +998,7 @@ pub struct Playlist {
 
 impl Playlist {
     pub fn from(playliststring: &str) -> Self {
-        let playliststring = RE_SPACES.replace_all(&playliststring, " ").to_string();
+        let playliststring = RE_SPACES.replace_all(playliststring, " ").to_string();
         let mut lines = playliststring.lines();
         Playlist {
             title: lines.next().unwrap().to_string(),
@@ -386,10 +1006,183 @@ impl Playlist {
         }
     }
 
+    /// Reads the title of a playlist without parsing the rest of it
     pub fn get_name(playliststring: &str) -> Option<String> {
-        match playliststring.lines().next() {
-            Some(line) => Some(line.to_string()),
-            None => None,
-        }
+        playliststring.lines().next().map(|line| line.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPO: &str = "{title: Capo}\n{key: A}\n{capo: 2}\n[A]One [D]two [E]three\n";
+
+    fn chords(content: &[SongLine]) -> Vec<String> {
+        content
+            .iter()
+            .flat_map(|line| line.blocks.iter())
+            .flat_map(|block| block.0.iter())
+            .filter_map(|string| match string {
+                SongString::Chord(chord) => Some(chord.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_capo() {
+        assert_eq!(Song::from(CAPO.to_owned()).capo, 2);
+        assert_eq!(Song::from(String::from("{capo: 15}")).capo, MAX_CAPO);
+        assert_eq!(Song::from(String::from("{capo: x}")).capo, 0);
+    }
+
+    #[test]
+    fn shows_shapes_for_a_capo() {
+        let song = Song::from(CAPO.to_owned());
+        assert_eq!(
+            chords(&song.with_capo(0, ChordDisplay::Shapes)),
+            ["A", "D", "E"]
+        );
+        assert_eq!(
+            chords(&song.with_capo(2, ChordDisplay::Concert)),
+            ["A", "D", "E"]
+        );
+        assert_eq!(
+            chords(&song.with_capo(2, ChordDisplay::Shapes)),
+            ["G", "C", "D"]
+        );
+        assert_eq!(
+            chords(&song.with_capo(2, ChordDisplay::Both)),
+            ["A(G)", "D(C)", "E(D)"]
+        );
+    }
+
+    #[test]
+    fn suggests_the_lowest_capo_with_open_shapes() {
+        let song = Song::from(String::from("[B]One [E]two [F#]three"));
+        assert_eq!(song.suggest_capo(None), Some((2, "A".parse().ok())));
+        assert_eq!(
+            Song::from(String::from("{title: Empty}")).suggest_capo(None),
+            None
+        );
+    }
+
+    #[test]
+    fn suggests_capos_for_a_target_key() {
+        let song = Song::from(String::from("{key: G}\n[G]One [C]two [D]three [Em]four"));
+        // Bb sounds with G shapes on the third fret
+        assert_eq!(
+            song.suggest_capo("Bb".parse().ok()),
+            Some((3, "G".parse().ok()))
+        );
+        assert_eq!(
+            song.suggest_capo("Gm".parse().ok()),
+            Some((3, "Em".parse().ok()))
+        );
+        assert_eq!(song.suggest_capo("G".parse().ok()), Some((0, song.key)));
+        let song = Song::from(String::from("{title: Empty}"));
+        assert_eq!(song.suggest_capo("A".parse().ok()), None);
+    }
+
+    #[test]
+    fn realises_number_chords_in_the_key() {
+        let song = Song::from(String::from("{key: G}\n[1]One [4]two [5/7]three [vi]four"));
+        assert_eq!(song.chords(), ["G", "C", "D/F#", "Em"]);
+    }
+
+    #[test]
+    fn realises_number_chords_before_a_late_key_tag() {
+        let song = Song::from(String::from("[1]One [4]two\n{key: D}\n[5]three [I]four"));
+        assert_eq!(song.chords(), ["D", "G", "A", "D"]);
+        let song = Song::in_key(
+            String::from("[1]One\n{key: D}\n[5]two"),
+            "E".parse().unwrap(),
+        );
+        assert_eq!(song.chords(), ["E", "B"]);
+    }
+
+    #[test]
+    fn realises_number_chords_in_the_estimated_key() {
+        let song = Song::from(String::from("[G]One [C]two [4]three [D]four [G]five"));
+        assert!(song.key_estimated);
+        assert_eq!(song.chords(), ["G", "C", "C", "D", "G"]);
+        // Without a key to realise them in, numbers are shown as they are written
+        let song = Song::from(String::from("[1]One [4]two [5]three"));
+        assert_eq!(song.key, None);
+        assert_eq!(song.chords(), ["1", "4", "5"]);
+    }
+
+    #[test]
+    fn writes_chords_relative_to_the_key() {
+        let song = Song::from(CAPO.to_owned());
+        assert_eq!(
+            song.with_notation(ChordNotation::Nashville)
+                .map(|content| chords(&content)),
+            Some(vec!["1".into(), "4".into(), "5".into()])
+        );
+        assert_eq!(
+            song.with_notation(ChordNotation::Roman)
+                .map(|content| chords(&content)),
+            Some(vec!["I".into(), "IV".into(), "V".into()])
+        );
+        let song = Song::from(String::from("{title: No chords}"));
+        assert!(song.with_notation(ChordNotation::Nashville).is_none());
+    }
+
+    #[test]
+    fn estimates_the_key_without_a_key_tag() {
+        let song = Song::from(String::from("[D]One [G]two [A]three [D]four"));
+        assert_eq!(song.key.map(|key| key.to_string()), Some("D".into()));
+        assert!(song.key_estimated);
+        assert!(!Song::from(CAPO.to_owned()).key_estimated);
+
+        let mut song = Song::from(String::from("{title: No chords}"));
+        assert_eq!(song.key, None);
+        song.change_key(2);
+        assert_eq!(song.key, None);
+    }
+
+    #[test]
+    fn reads_minor_keys() {
+        let song = Song::from(String::from("{key: Am}\n[Am]One [Dm]two [E7]three"));
+        assert_eq!(song.key.map(|key| key.to_string()), Some("Am".into()));
+        let song = Song::in_key(
+            String::from("{key: Am}\n[Am]One [Dm]two"),
+            "C".parse().unwrap(),
+        );
+        assert_eq!(song.key.map(|key| key.to_string()), Some("Am".into()));
+        let song = Song::in_key(
+            String::from("{key: Dm}\n[Dm]One [A#]two"),
+            "Dm".parse().unwrap(),
+        );
+        assert_eq!(song.chords(), ["Dm", "Bb"]);
+    }
+
+    #[test]
+    fn sets_keys_across_modes() {
+        let mut song = Song::from(String::from("{key: G}\n[G]One [D]two"));
+        song.set_key("A".parse().unwrap());
+        assert_eq!(song.chords(), ["A", "E"]);
+        // The relative minor of A is F#m, which has the same key signature
+        song.set_key("F#m".parse().unwrap());
+        assert_eq!(song.chords(), ["A", "E"]);
+        song.set_key("Em".parse().unwrap());
+        assert_eq!(song.chords(), ["G", "D"]);
+    }
+
+    #[test]
+    fn reads_tempo_and_tab() {
+        let song = Song::from(String::from("{tempo: 96}\n{sot}\nE|-[3]-|\n{eot}\n[G]Sing"));
+        assert_eq!(song.tempo, Some(96));
+        assert_eq!(song.chords(), ["G"]);
+    }
+
+    #[test]
+    fn splits_files_at_new_song() {
+        let songs = Song::split("{title: One}\n[G]Sing\n{new_song}\n\n{title: Two}\n  {ns}  \r\n");
+        assert_eq!(songs, ["{title: One}\n[G]Sing\n", "{title: Two}\n"]);
+        assert_eq!(Song::split("{title: One}\n"), ["{title: One}\n"]);
+        assert_eq!(Song::split("{ns}\n"), ["{ns}\n"]);
     }
 }
@@ -1,16 +1,58 @@
 use crate::{
-    app::{App, FileType},
+    app::{App, AppState},
     conf::Theme,
+    key::{Key, Mode},
+    library::FileType,
     parser::*,
 };
+use rust_music_theory::note::PitchClass;
+use std::ops::Range;
 use tui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Style},
     text::{Span, Spans, Text},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
     Frame,
 };
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Draws the whole interface: the song list or the panel of the current state on the left, and
+/// the selected song on the right
+pub fn draw<B>(f: &mut Frame<B>, app: &mut App)
+where
+    B: Backend,
+{
+    let layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .margin(1)
+        .constraints([Constraint::Length(20), Constraint::Min(80)].as_ref())
+        .split(f.size());
+
+    let left_bar = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Max(100), Constraint::Length(3)])
+        .split(layout[0]);
+
+    let key_picker_bar = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Max(100), Constraint::Length(14)])
+        .split(layout[0]);
+
+    match app.state {
+        AppState::Default => draw_song_list(f, app, layout[0]),
+        AppState::Searching => {
+            draw_song_list(f, app, left_bar[0]);
+            draw_search_bar(f, app, left_bar[1]);
+        }
+        AppState::Transposing => {
+            draw_song_list(f, app, key_picker_bar[0]);
+            draw_transposition(f, app, key_picker_bar[1]);
+        }
+        AppState::Importing => draw_import(f, app, layout[0]),
+    }
+    draw_song(f, app, layout[1]);
+}
 
 pub fn draw_song_list<B>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect)
 where
@@ -61,12 +103,12 @@ where
     B: Backend,
 {
     // Only show last characters that fit in search box
-    let inner_size = (layout_chunk.width - 3) as usize; // Two border pixels, one cursor pixel
+    let inner_size = layout_chunk.width.saturating_sub(3) as usize; // Two border pixels, one cursor pixel
     let input = &app.input;
-    let input_length = input.chars().count();
     let mut inputtext = &input[..];
-    if input_length >= inner_size {
-        inputtext = &input[input_length - inner_size..];
+    while display_width(inputtext) > inner_size {
+        let first = inputtext.graphemes(true).next().unwrap();
+        inputtext = &inputtext[first.len()..];
     }
 
     // Add cursor if search box is selected
@@ -90,43 +132,135 @@ pub fn draw_transposition<B>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect
 where
     B: Backend,
 {
-    let transpose_block = Block::default()
-        .borders(Borders::ALL)
-        .border_style(app.config.theme.selected.to_style())
-        .title(Span::from("Transpose"));
-
-    let transpose = Paragraph::new(Text::from(match &app.song {
-        Some(song) => match song.key {
-            Some(key) => key.to_string(),
-            None => String::from("No key found"),
-        },
-        None => String::from("No song selected"),
-    }))
-    .block(transpose_block);
-    //    match &app.song {
-    //        Some(song) => {
-    //            let key_block = List::new()
-    //        }
-    //        None => (),
-    //    }
+    let song = match &app.song {
+        Some(song) => song,
+        None => return draw_transposition_message(f, app, layout_chunk, "No song selected"),
+    };
+    let key = match song.key {
+        Some(key) => key,
+        None => return draw_transposition_message(f, app, layout_chunk, "No key found"),
+    };
+
+    // List every key with its relative major or minor and the shapes to play it with a capo. Without a
+    // capo, the fret that allows open shapes is suggested instead.
+    let capo = app.capo();
+    let keys: Vec<ListItem> = (0..12)
+        .map(|i| {
+            let row_key = Key::new(PitchClass::from_u8(i), key.mode);
+            let marker = match song.original_key.map(|key| key.tonic) == Some(row_key.tonic) {
+                true => "*",
+                false => " ",
+            };
+            let name = match key.mode {
+                Mode::Major | Mode::Minor => format!("{}/{}", row_key, row_key.relative()),
+                _ => row_key.to_string(),
+            };
+            let capo = match capo {
+                0 => row_key.open_shape_capo(),
+                capo => Some((capo, row_key.transpose(-capo))),
+            };
+            let capo = match capo {
+                Some((capo, shape_key)) if capo > 0 => format!("capo {}: {}", capo, shape_key),
+                _ => String::new(),
+            };
+            let style = match row_key.tonic == key.tonic {
+                true => app.config.theme.selected.to_style(),
+                false => Style::default(),
+            };
+            ListItem::new(Span::styled(
+                format!("{}{:<7}{}", marker, name, capo),
+                style,
+            ))
+        })
+        .collect();
+
+    let title = match song.key_estimated {
+        true => "Key (estimated)",
+        false => "Key",
+    };
+    let key_picker = List::new(keys)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(app.config.theme.selected.to_style())
+                .title(Span::from(title)),
+        )
+        .highlight_style(Style::default().bg(Color::DarkGray));
+
+    f.render_stateful_widget(key_picker, layout_chunk, &mut app.key_picker)
+}
+
+fn draw_transposition_message<B>(f: &mut Frame<B>, app: &App, layout_chunk: Rect, message: &str)
+where
+    B: Backend,
+{
+    let transpose = Paragraph::new(Text::from(message)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(app.config.theme.selected.to_style())
+            .title(Span::from("Key")),
+    );
     f.render_widget(transpose, layout_chunk)
 }
 
+pub fn draw_import<B>(f: &mut Frame<B>, app: &App, layout_chunk: Rect)
+where
+    B: Backend,
+{
+    let file = app
+        .import
+        .as_ref()
+        .and_then(|(path, _)| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let text = vec![
+        Spans::from("Save to"),
+        Spans::from(Span::styled(file, app.config.theme.song.to_style())),
+        Spans::default(),
+        Spans::from("Enter: save"),
+        Spans::from("Esc: discard"),
+    ];
+    let import = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(app.config.theme.selected.to_style())
+            .title(Span::from("Import")),
+    );
+    f.render_widget(import, layout_chunk)
+}
+
 pub fn draw_song<B>(f: &mut Frame<B>, app: &App, layout_chunk: Rect)
 where
     B: Backend,
 {
     match &app.song {
         Some(song) => {
+            let capo = app.capo();
+            let mut title = match song.subtitle.is_empty() {
+                true => song.title.clone(),
+                false => format!("{} - {}", song.title, song.subtitle),
+            };
+            if capo > 0 {
+                title += &match song.key {
+                    Some(key) => format!(" | Capo {} (sounds in {})", capo, key),
+                    None => format!(" | Capo {}", capo),
+                };
+            }
+            if let Some(tempo) = song.tempo {
+                title += &format!(" | {} BPM", tempo);
+            }
             let song_block = Block::default()
-                .title(Span::styled(
-                    format!("{} - {}", song.title.as_str(), song.subtitle.as_str()),
-                    app.config.theme.title.to_style(),
-                ))
+                .title(Span::styled(title, app.config.theme.title.to_style()))
                 .borders(Borders::ALL);
 
             let song_rect = song_block.inner(layout_chunk);
-            let text = wrap_lines(&song.content, song_rect, app.config.extra_column_size);
+            let content = song.display(capo, &app.config);
+            let text = wrap_lines(
+                &content,
+                song_rect,
+                app.config.extra_column_size,
+                app.config.column_padding,
+            );
 
             let constraints: Vec<Constraint> = text
                 .iter()
@@ -138,7 +272,7 @@ where
             let song_layout = Layout::default()
                 .direction(Direction::Horizontal)
                 .margin(1)
-                .constraints(constraints.as_ref())
+                .constraints(constraints)
                 .split(layout_chunk);
 
             for (i, column) in song_layout.iter().enumerate() {
@@ -153,6 +287,8 @@ where
     }
 }
 
+/// A column of lines of a song, which are shown next to each other when a song doesn't fit on
+/// the screen
 #[derive(Debug, Default)]
 pub struct Column {
     content: Vec<SongLine>,
@@ -163,6 +299,11 @@ impl<'a> Column {
         Column { content }
     }
 
+    /// The lines in the column
+    pub fn lines(&self) -> &[SongLine] {
+        &self.content
+    }
+
     pub fn width(&self) -> usize {
         self.content
             .iter()
@@ -180,38 +321,113 @@ impl<'a> Column {
     }
 }
 
-pub fn wrap_lines(lines: &[SongLine], container: Rect, extra_column_size: usize) -> Vec<Column> {
-    let height = (container.height - 2) as usize;
+/// Wraps the lines of a song and divides them into columns of about the same height that fit in
+/// the container. Columns are as wide as they can be while still fitting the whole song on the
+/// screen, but no narrower than the median line. Songs that don't fit get columns
+/// `extra_column_size` wider than the median line, which run off the screen.
+pub fn wrap_lines(
+    lines: &[SongLine],
+    container: Rect,
+    extra_column_size: usize,
+    column_padding: usize,
+) -> Vec<Column> {
     if lines.is_empty() {
         return vec![];
     }
+    let height = (container.height as usize).max(1);
+    let screen_width = container.width as usize;
 
     let mut line_widths: Vec<usize> = lines.iter().map(|line| line.width()).collect();
     line_widths.sort_unstable();
-    let median_width = line_widths[line_widths.len() / 2];
+    let widest = *line_widths.last().unwrap();
+    let median = line_widths[line_widths.len() / 2];
 
-    let line_wrapped_text: Vec<SongLine> = lines
-        .iter()
-        .flat_map(|line| line.wrap(median_width + extra_column_size))
-        .collect();
+    let wrap = |width: usize| -> Vec<SongLine> {
+        lines.iter().flat_map(|line| line.wrap(width)).collect()
+    };
+    let fitting = (1..=screen_width / (median + column_padding).max(1)).find_map(|count| {
+        let wrapped = wrap((screen_width / count - column_padding).min(widest));
+        balance_columns(&wrapped, height, count).map(|columns| (wrapped, columns))
+    });
+    let (wrapped, columns) = fitting.unwrap_or_else(|| {
+        let wrapped = wrap(
+            (median + extra_column_size)
+                .min(widest)
+                .min(screen_width.saturating_sub(column_padding))
+                .max(1),
+        );
+        let count = split_columns(&wrapped, height, height).len();
+        let columns = balance_columns(&wrapped, height, count).unwrap();
+        (wrapped, columns)
+    });
+    columns
+        .into_iter()
+        .map(|range| Column::from(wrapped[range].to_vec()))
+        .collect()
+}
 
-    let mut column_wrapped_text: Vec<Column> = vec![];
-    let mut columnheight = 0;
-    let mut rest = &line_wrapped_text[..];
-    let mut i = 0;
-    line_wrapped_text.iter().for_each(|line| {
-        if columnheight + line.height() > height {
-            let split_text = rest.split_at(i);
-            rest = split_text.1;
-            column_wrapped_text.push(Column::from(split_text.0.to_vec()));
-            columnheight = 0;
-            i = 0;
-        } else {
-            columnheight += line.height();
-            i += 1;
+/// Divides lines into at most `count` columns no taller than the given height. Columns are made
+/// as short as they can be, so they are about the same height.
+fn balance_columns(lines: &[SongLine], height: usize, count: usize) -> Option<Vec<Range<usize>>> {
+    let total: usize = lines.iter().map(SongLine::height).sum();
+    let shortest = (total / count.max(1)).clamp(1, height);
+    (shortest..=height)
+        .map(|column_height| split_columns(lines, column_height, height))
+        .find(|columns| columns.len() <= count)
+}
+
+/// Divides lines into columns of about the given height, starting a new column wherever the song
+/// asks for a break. Sections are kept in one column, which may make it taller, unless they are
+/// taller than `max_height`. Those are split between lines, rather than within a wrapped line
+/// where possible.
+fn split_columns(lines: &[SongLine], column_height: usize, max_height: usize) -> Vec<Range<usize>> {
+    let height = |range: Range<usize>| -> usize { lines[range].iter().map(SongLine::height).sum() };
+    let mut columns = vec![];
+    let mut start = 0;
+    for section in sections(lines) {
+        let section_height = height(section.clone());
+        let forced = lines[section.start].break_before.is_some();
+        if start < section.start
+            && (forced || height(start..section.start) + section_height > column_height)
+        {
+            columns.push(start..section.start);
+            start = section.start;
         }
-    });
-    column_wrapped_text.push(Column::from(rest.to_vec()));
+        if section_height <= max_height {
+            continue;
+        }
+        for end in section.clone() {
+            if start < end && height(start..end + 1) > column_height {
+                // Keep wrapped lines together if that leaves the column at least half full
+                let line_start = (start + 1..=end)
+                    .rev()
+                    .take_while(|&line| height(start..line) * 2 >= column_height)
+                    .find(|&line| !lines[line].continued)
+                    .unwrap_or(end);
+                columns.push(start..line_start);
+                start = line_start;
+            }
+        }
+    }
+    columns.push(start..lines.len());
+    columns
+}
 
-    column_wrapped_text
+/// Divides lines into sections, which end after blank lines, where a chorus starts or ends, and
+/// where the song asks for a break
+fn sections(lines: &[SongLine]) -> Vec<Range<usize>> {
+    let mut sections = vec![];
+    let mut start = 0;
+    for (i, pair) in lines.windows(2).enumerate() {
+        let (line, next) = (&pair[0], &pair[1]);
+        let boundary = next.break_before.is_some()
+            || (line.blocks.is_empty() && !next.blocks.is_empty())
+            || (line.chorus != next.chorus && !next.continued);
+        if boundary {
+            sections.push(start..i + 1);
+            start = i + 1;
+        }
+    }
+    sections.push(start..lines.len());
+    sections
 }
@@ -0,0 +1,27 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A temporary directory that is removed when dropped, even if a test panics
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates an empty directory unique to the test name and process
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("gpro-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,101 @@
+use gpro::{
+    conf::Config,
+    export::{self, Format},
+    parser::Song,
+};
+
+fn song() -> Song {
+    Song::from(String::from(
+        "{title: Amazing Grace}\n{key: G}\n\nA[G]mazing [C]grace\n",
+    ))
+}
+
+fn export(format: Format) -> String {
+    String::from_utf8(export::export(
+        &[song()],
+        None,
+        format,
+        80,
+        &Config::default(),
+    ))
+    .unwrap()
+}
+
+#[test]
+fn parses_formats() {
+    assert_eq!("md".parse::<Format>().unwrap(), Format::Markdown);
+    assert_eq!("LaTeX".parse::<Format>().unwrap(), Format::Latex);
+    assert!("docx".parse::<Format>().is_err());
+    assert_eq!(Format::OpenLyrics.extension(), "xml");
+}
+
+#[test]
+fn exports_text() {
+    let text = export(Format::Text);
+    assert!(text.contains("Amazing Grace"));
+    assert!(text.contains(" G      C\nAmazing grace"));
+}
+
+#[test]
+fn exports_html() {
+    let html = export(Format::Html);
+    assert!(html.contains("<html"));
+    assert!(html.contains("Amazing Grace"));
+}
+
+#[test]
+fn exports_openlyrics() {
+    let xml = export(Format::OpenLyrics);
+    assert!(xml.contains("<chord name=\"G\"/>"));
+    assert!(xml.contains("<key>G</key>"));
+}
+
+#[test]
+fn exports_pdf() {
+    assert!(
+        export::export(&[song()], None, Format::Pdf, 80, &Config::default()).starts_with(b"%PDF")
+    );
+}
+
+fn export_song(chordpro: &str, format: Format) -> String {
+    let song = Song::from(chordpro.to_owned());
+    String::from_utf8(export::export(
+        &[song],
+        None,
+        format,
+        80,
+        &Config::default(),
+    ))
+    .unwrap()
+}
+
+const RECALL: &str = "{title: Grace}\n{soc}\n[G]Glory\n{eoc}\n\n[C]Verse one\n\n{chorus}\n\n\
+                      [C]Verse two\n\n{chorus}\n";
+
+#[test]
+fn repeats_recalled_choruses_in_the_order() {
+    let opensong = export_song(RECALL, Format::OpenSong);
+    assert!(
+        opensong.contains("<presentation>C1 V1 C1 V2 C1</presentation>"),
+        "{}",
+        opensong
+    );
+    assert!(!opensong.contains("[C2]"));
+    let openlyrics = export_song(RECALL, Format::OpenLyrics);
+    assert!(
+        openlyrics.contains("<verseOrder>c1 v1 c1 v2 c1</verseOrder>"),
+        "{}",
+        openlyrics
+    );
+    assert_eq!(openlyrics.matches("<verse name=").count(), 3);
+}
+
+#[test]
+fn leaves_out_lines_for_other_instruments() {
+    let chordpro = "{title: Grace}\n{c-keys: Keys intro}\n{c-guitar: Capo 2}\n[G]Glory\n";
+    for format in [Format::OpenSong, Format::OpenLyrics] {
+        let exported = export_song(chordpro, format);
+        assert!(!exported.contains("Keys"), "{}", exported);
+        assert!(exported.contains("Capo"), "{}", exported);
+    }
+}
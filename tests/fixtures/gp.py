@@ -0,0 +1,78 @@
+# Writes the Guitar Pro fixtures: python3 gp.py 300 song.gp3, 400 song.gp4, 500 song.gp5 and
+# 510 song-5.10.gp5. Each song has two measures of chords and tab with lyrics, a marker and a
+# drum track.
+import struct, sys
+def b(v): return struct.pack('<B', v & 0xff)
+def i8(v): return struct.pack('<b', v)
+def i16(v): return struct.pack('<h', v)
+def i32(v): return struct.pack('<i', v)
+def bss(s, n): e=s.encode(); return b(len(e)) + e + b'\0'*(n-len(e))
+def ibss(s): e=s.encode(); return i32(len(e)+1) + b(len(e)) + e
+def iss(s): e=s.encode(); return i32(len(e)) + e
+
+def song(v):
+    gp5 = v >= 500; gp4 = v >= 400
+    name = {300:"FICHIER GUITAR PRO v3.00",400:"FICHIER GUITAR PRO v4.06",500:"FICHIER GUITAR PRO v5.00",510:"FICHIER GUITAR PRO v5.10"}[v]
+    d = bss(name, 30)
+    infos = ["Test Song", "", "The Band"] + [""] * (6 if gp5 else 5)
+    d += b''.join(ibss(s) for s in infos) + i32(0)
+    if not gp5: d += b(0)
+    if gp4:
+        d += i32(1) + i32(1) + iss("Hel-lo world\nsing a-long") + b''.join(i32(0)+iss("") for _ in range(4))
+    if v >= 510: d += b'\0'*19
+    if gp5: d += b'\0'*30 + b''.join(ibss("") for _ in range(10)) + ibss("Moderate")
+    d += i32(96)
+    if v >= 510: d += b(0)
+    d += i32(0)
+    if gp4: d += b(0)
+    d += b'\0'*(64*12)
+    if gp5: d += b'\0'*38 + i32(0)
+    d += i32(2) + i32(2)
+    # measure 1: time sig, marker, key G
+    for n in range(2):
+        if gp5 and n > 0: d += b(0)
+        if n == 0:
+            d += b(0x01|0x02|0x20|0x40) + b(4) + b(4) + ibss("Verse") + b'\0'*4 + i8(1) + b(0)
+            if gp5: d += b'\0'*4 + b(0) + b(0)
+        else:
+            d += b(0)
+            if gp5: d += b(0) + b(0)
+    for t in range(2):
+        if gp5 and (t == 0 or v == 500): d += b(0)
+        d += b(0x01 if t == 1 else 0) + bss("Guitar" if t == 0 else "Drums", 40) + i32(6)
+        d += b''.join(i32(x) for x in [64,59,55,50,45,40,0]) + b'\0'*24
+        if gp5:
+            d += b'\0'*(2+1+1+1+12+12+12)
+            d += b'\0'*3 if v == 500 else b'\0'*4 + b'\0'*4 + ibss("") + ibss("")
+    if gp5: d += b'\0'*(2 if v == 500 else 1)
+    def chord(name):
+        if gp5: return b'\0'*17 + bss(name, 21) + b'\0'*4 + i32(0) + b'\0'*28 + b'\0'*32
+        return b(0) + ibss(name) + i32(0)
+    def note(fret, kind=1):
+        r = b(0x20) + b(kind) + i8(fret)
+        if gp5: r += b(0)
+        return r
+    def beat(ch, frets, dur=0, effect=False):
+        flags = (0x02 if ch else 0)
+        r = b(flags) + i8(dur)
+        if ch: r += chord(ch)
+        sf = 0
+        for s in frets: sf |= 1 << (6 - s)
+        r += b(sf)
+        for s in sorted(frets): r += note(frets[s], 3 if frets[s] == 99 else 1) if frets[s] != 99 else note(0, 3)
+        if gp5: r += i16(0)
+        return r
+    for m in range(2):
+        for t in range(2):
+            beats = []
+            if t == 0:
+                if m == 0:
+                    beats = [beat("G", {0:3, 5:3}), beat(None, {1:0}), beat("C", {1:1, 4:3}), beat(None, {2:99})]
+                else:
+                    beats = [beat("D", {0:2}), beat(None, {1:3}), beat("Em", {2:0}), beat(None, {3:12}), beat(None, {3:10}, dur=1), beat(None, {3:9}, dur=1)]
+            voice = i32(len(beats)) + b''.join(beats)
+            d += voice
+            if gp5: d += i32(0) + b(0)
+    return d
+v = int(sys.argv[1])
+open(sys.argv[2], 'wb').write(song(v))
@@ -0,0 +1,75 @@
+use gpro::{
+    import::{self, Format},
+    parser::{Song, SongString},
+};
+
+#[test]
+fn keeps_opensong_lyrics_without_a_leading_space() {
+    let song = "<song><title>Amazing Grace</title><lyrics>[V1]\n\
+                .G\n Amazing grace\nHow sweet the sound\n.C\nThat saved a wretch\n\
+                </lyrics></song>";
+    let chordpro = import::import(song.as_bytes(), Format::OpenSong).unwrap();
+    assert!(chordpro.contains("[G]Amazing grace\n"), "{}", chordpro);
+    assert!(chordpro.contains("\nHow sweet the sound\n"), "{}", chordpro);
+    assert!(
+        chordpro.contains("[C]That saved a wretch\n"),
+        "{}",
+        chordpro
+    );
+}
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(format!("tests/fixtures/{}", name)).unwrap()
+}
+
+#[test]
+fn rejects_malformed_guitar_pro_files() {
+    for file in ["song.gp3", "song.gp4", "song.gp5", "song-5.10.gp5"] {
+        let contents = fixture(file);
+        for length in 0..contents.len() {
+            assert!(import::import(&contents[..length], Format::GuitarPro).is_err());
+        }
+        // Extreme values anywhere in the file give an error or a song, but never a panic
+        for position in 0..contents.len() {
+            for byte in [0x7e, 0x7f, 0x80, 0xff] {
+                let mut contents = contents.clone();
+                contents[position] = byte;
+                let _ = import::import(&contents, Format::GuitarPro);
+            }
+        }
+    }
+}
+
+#[test]
+fn keeps_no_chord_when_transposing() {
+    let chordpro = import::import(&fixture("lead.musicxml"), Format::MusicXml).unwrap();
+    let mut song = Song::from(chordpro);
+    song.set_key("G".parse().unwrap());
+    assert_eq!(
+        song.chords(),
+        vec!["G", "C7/E", "Dsus4add9", "Gmaj7", "G", "C7/E"]
+    );
+    let line = song
+        .content
+        .iter()
+        .find(|line| line.chords().contains(&"Gmaj7"));
+    assert!(line
+        .unwrap()
+        .blocks
+        .iter()
+        .flat_map(|block| block.0.iter())
+        .any(|string| matches!(string, SongString::Annotation(text) if text == "N.C.")));
+}
+
+#[test]
+fn reads_musicxml_with_huge_durations() {
+    let score = String::from_utf8(fixture("lead.musicxml"))
+        .unwrap()
+        .replace("<duration>4</duration>", "<duration>4294967295</duration>")
+        .replace(
+            "<harmony><kind>none</kind>",
+            "<harmony><offset>-2147483648</offset><kind>none</kind>",
+        );
+    let chordpro = import::import(score.as_bytes(), Format::MusicXml).unwrap();
+    assert!(chordpro.contains("[F]Happy"));
+}
@@ -0,0 +1,37 @@
+use gpro::key::{Key, Mode};
+
+#[test]
+fn parses_keys() {
+    let key: Key = "F#m".parse().unwrap();
+    assert_eq!(key.mode, Mode::Minor);
+    assert_eq!(key.to_string(), "F#m");
+    assert_eq!("Bb".parse::<Key>().unwrap().to_string(), "Bb");
+    assert_eq!("A minor".parse::<Key>().unwrap().to_string(), "Am");
+    assert_eq!("D dorian".parse::<Key>().unwrap().mode, Mode::Dorian);
+    assert!("H".parse::<Key>().is_err());
+}
+
+#[test]
+fn transposes_keys() {
+    let key: Key = "G".parse().unwrap();
+    assert_eq!(key.transpose(3).to_string(), "Bb");
+    assert_eq!(key.transpose(-8).to_string(), "B");
+    assert_eq!(key.relative().to_string(), "Em");
+}
+
+#[test]
+fn reads_key_signatures() {
+    assert_eq!(Key::from_fifths(0, false).to_string(), "C");
+    assert_eq!(Key::from_fifths(3, false).to_string(), "A");
+    assert_eq!(Key::from_fifths(-2, false).to_string(), "Bb");
+    assert_eq!(Key::from_fifths(1, true).to_string(), "Em");
+}
+
+#[test]
+fn finds_capo_for_open_shapes() {
+    let key: Key = "Bb".parse().unwrap();
+    let (capo, shapes) = key.open_shape_capo().unwrap();
+    assert_eq!(capo, 1);
+    assert_eq!(shapes.to_string(), "A");
+    assert_eq!("G".parse::<Key>().unwrap().open_shape_capo().unwrap().0, 0);
+}
@@ -0,0 +1,57 @@
+use common::TempDir;
+use gpro::{
+    import::{self, Format},
+    library::{FileType, Library},
+};
+use std::fs;
+
+mod common;
+
+/// Creates a library directory with a song file holding two songs and a playlist
+fn library(name: &str) -> TempDir {
+    let dir = TempDir::new(name);
+    let path = dir.path();
+    fs::create_dir_all(path.join("hymns")).unwrap();
+    fs::write(
+        path.join("hymns").join("hymns.txt"),
+        "{title: Amazing Grace}\n{key: G}\n[G]Amazing [C]grace\n{new_song}\n{title: Second}\n[D]Two\n",
+    )
+    .unwrap();
+    fs::write(path.join("set.lst"), "Sunday\nAmazing Grace [A]\nMissing\n").unwrap();
+    fs::write(path.join("notes.md"), "Not a song").unwrap();
+    dir
+}
+
+#[test]
+fn scans_songs_and_playlists() {
+    let dir = library("scan");
+    let path = dir.path();
+    let library = Library::scan(path);
+    let names: Vec<String> = library.songs().into_iter().map(|(name, _)| name).collect();
+    assert_eq!(names, vec!["Amazing Grace", "Second"]);
+    assert!(library
+        .files()
+        .contains_key(&FileType::Folder(path.join("hymns"))));
+    let playlists = library.playlists();
+    assert_eq!(playlists.len(), 1);
+    assert_eq!(playlists[0].title, "Sunday");
+}
+
+#[test]
+fn finds_songs_in_a_key() {
+    let dir = library("find");
+    let library = Library::scan(dir.path());
+    let song = library.find_song("Amazing Grace [A]").unwrap();
+    assert_eq!(song.chords(), vec!["A", "D"]);
+    let song = library.find_song("Amazing Grace [A] {capo: 2}").unwrap();
+    assert_eq!((song.chords(), song.capo), (vec!["A", "D"], 2));
+    assert_eq!(library.find_song("Second {capo: 3}").unwrap().capo, 3);
+    assert!(library.find_song("Missing").is_none());
+}
+
+#[test]
+fn imports_chord_sheets() {
+    let chordpro = import::import(b"[Verse]\nG       C\nAmazing grace\n", Format::Text).unwrap();
+    assert!(chordpro.contains("[G]Amazing [C]grace"));
+    assert!(chordpro.contains("{c: Verse}"));
+}
@@ -0,0 +1,338 @@
+use gpro::{
+    conf::{ChorusRecall, Config, Theme},
+    key::Key,
+    parser::{Playlist, Selection, Song, SongLine, SongString},
+};
+
+const SONG: &str = "{title: Amazing Grace}\n{subtitle: John Newton}\n{key: G}\n{capo: 2}\n\n\
+                    A[G]mazing [G7]grace, how [C]sweet the [G]sound\n\
+                    {soc}\n[Em]I once was [D]lost\n{eoc}\n";
+
+#[test]
+fn reads_directives() {
+    let song = Song::from(SONG.to_owned());
+    assert_eq!(song.title, "Amazing Grace");
+    assert_eq!(song.subtitle, "John Newton");
+    assert_eq!(song.key, Some("G".parse().unwrap()));
+    assert_eq!(song.capo, 2);
+    assert!(!song.key_estimated);
+}
+
+#[test]
+fn reads_chords_and_lyrics() {
+    let song = Song::from(SONG.to_owned());
+    assert_eq!(song.chords(), vec!["G", "G7", "C", "G", "Em", "D"]);
+    let line = song
+        .content
+        .iter()
+        .find(|line| !line.chords().is_empty())
+        .unwrap();
+    let text: String = line
+        .blocks
+        .iter()
+        .flat_map(|block| block.0.iter())
+        .filter_map(|string| match string {
+            SongString::Text(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(text.trim_end(), "Amazing grace, how sweet the sound");
+    assert!(!line.chorus);
+}
+
+#[test]
+fn marks_choruses() {
+    let song = Song::from(SONG.to_owned());
+    let chorus = song
+        .content
+        .iter()
+        .find(|line| line.chords() == vec!["Em", "D"])
+        .unwrap();
+    assert!(chorus.chorus);
+}
+
+#[test]
+fn transposes() {
+    let mut song = Song::from(SONG.to_owned());
+    song.change_key(2);
+    assert_eq!(song.key, Some("A".parse().unwrap()));
+    assert_eq!(song.chords(), vec!["A", "A7", "D", "A", "F#m", "E"]);
+
+    let song = Song::in_key(SONG.to_owned(), "F".parse().unwrap());
+    assert_eq!(song.chords(), vec!["F", "F7", "Bb", "F", "Dm", "C"]);
+}
+
+#[test]
+fn sets_key_across_modes() {
+    let mut song = Song::from(SONG.to_owned());
+    song.set_key(Key::from_fifths(0, true));
+    assert_eq!(song.key, Some("C".parse().unwrap()));
+    assert_eq!(song.chords()[0], "C");
+}
+
+#[test]
+fn wraps_lines() {
+    let song = Song::from(SONG.to_owned());
+    let line = song
+        .content
+        .iter()
+        .find(|line| !line.chords().is_empty())
+        .unwrap();
+    let wrapped = line.wrap(20);
+    assert!(wrapped.len() > 1);
+    assert!(wrapped.iter().all(|line| line.width() <= 20));
+    let chords: Vec<&str> = wrapped.iter().flat_map(|line| line.chords()).collect();
+    assert_eq!(chords, line.chords());
+}
+
+#[test]
+fn names_songs() {
+    assert_eq!(
+        Song::get_name(SONG).as_deref(),
+        Some("Amazing Grace - John Newton")
+    );
+    assert_eq!(Song::get_name("[C]No title"), None);
+}
+
+#[test]
+fn splits_files_with_several_songs() {
+    let songs = Song::split("{title: One}\n[C]One\n{new_song}\n{title: Two}\n{ns}\n");
+    assert_eq!(songs.len(), 2);
+    assert_eq!(Song::get_name(&songs[1]).as_deref(), Some("Two"));
+    assert_eq!(Song::split("{title: One}\n").len(), 1);
+}
+
+#[test]
+fn reads_playlists() {
+    let playlist = Playlist::from("Sunday\nAmazing Grace - John Newton [A]\nSecond\n");
+    assert_eq!(playlist.title, "Sunday");
+    let names: Vec<String> = playlist.songs.iter().map(|song| song.name()).collect();
+    assert_eq!(names, vec!["Amazing Grace - John Newton [A]", "Second"]);
+}
+
+#[test]
+fn serializes_to_json() {
+    let song = Song::from(SONG.to_owned());
+    let json = serde_json::to_value(&song).unwrap();
+    assert_eq!(json["title"], "Amazing Grace");
+    assert_eq!(json["key"], "G");
+    assert_eq!(json["content"][1]["blocks"][0][1]["chord"], "G");
+
+    let song: Song = serde_json::from_value(json).unwrap();
+    assert_eq!(song.key, Some("G".parse().unwrap()));
+    assert_eq!(song.chords(), vec!["G", "G7", "C", "G", "Em", "D"]);
+}
+
+/// The rows a line is shown as, without styles or trailing spaces
+fn rows(line: &SongLine) -> Vec<String> {
+    line.format(&Theme::default())
+        .iter()
+        .map(|spans| {
+            let row: String = spans.0.iter().map(|span| span.content.as_ref()).collect();
+            row.trim_end().to_owned()
+        })
+        .collect()
+}
+
+fn first_line(chordpro: &str) -> SongLine {
+    Song::from(chordpro.to_owned()).content.remove(0)
+}
+
+#[test]
+fn indents_continued_lines() {
+    let line = first_line("[G]Through many [C]dangers, toils and [G]snares\n");
+    let wrapped = line.wrap(24);
+    assert!(!wrapped[0].continued);
+    assert!(wrapped[1..].iter().all(|line| line.continued));
+    let rows: Vec<String> = wrapped.iter().flat_map(rows).collect();
+    assert_eq!(
+        rows,
+        vec![
+            "G            C",
+            "Through many dangers,",
+            "            G",
+            "  toils and snares",
+        ]
+    );
+}
+
+#[test]
+fn keeps_chords_over_spaces_with_the_next_word() {
+    let line = first_line("I once was [D] [G]lost, but now am found\n");
+    let wrapped = line.wrap(14);
+    let rows: Vec<String> = wrapped.iter().flat_map(rows).collect();
+    assert_eq!(
+        rows,
+        vec!["I once was", "  D G", "    lost, but", "  now am found"]
+    );
+}
+
+#[test]
+fn splits_long_words_before_chords() {
+    let line = first_line("[Em]Supercali[C]fragilistic[D]expiali[G]docious\n");
+    let wrapped = line.wrap(16);
+    let rows: Vec<String> = wrapped.iter().flat_map(rows).collect();
+    assert_eq!(
+        rows,
+        vec![
+            "Em",
+            "Supercali-",
+            "  C",
+            "  fragilistic-",
+            "  D      G",
+            "  expialidocious",
+        ]
+    );
+}
+
+#[test]
+fn wraps_choruses_to_the_same_width() {
+    let song = Song::from(String::from(
+        "{soc}\n[C]Praise the Lord, [G]praise the Lord, oh my [D]soul\n{eoc}\n",
+    ));
+    for wrapped in song.content[0].wrap(22) {
+        assert!(wrapped.chorus);
+        assert!(wrapped.width() <= 22, "{:?}", rows(&wrapped));
+    }
+}
+
+const RECALL: &str = "{soc}\n[G]Glory, [C]glory\n[D]Hallelujah\n{eoc}\n\n\
+                      {start_of_verse: Verse 2}\n[Em]Praise him\n{end_of_verse}\n\n\
+                      [G]Verse\n{chorus}\n{chorus: Verse 2}\n";
+
+fn recalled(recall: ChorusRecall) -> Vec<String> {
+    Song::from(RECALL.to_owned())
+        .display(
+            0,
+            &Config {
+                chorus_recall: recall,
+                ..Default::default()
+            },
+        )
+        .iter()
+        .skip_while(|line| rows(line) != vec!["G", "Verse"])
+        .skip(1)
+        .flat_map(rows)
+        .collect()
+}
+
+#[test]
+fn recalls_choruses() {
+    assert_eq!(
+        recalled(ChorusRecall::Full),
+        vec![
+            "| G      C",
+            "| Glory, glory",
+            "| D",
+            "| Hallelujah",
+            "Verse 2",
+            "Em",
+            "Praise him",
+        ]
+    );
+    assert_eq!(recalled(ChorusRecall::Label), vec!["| Chorus", "Verse 2"]);
+    assert_eq!(
+        recalled(ChorusRecall::FirstLine),
+        vec![
+            "| Chorus",
+            "| G      C",
+            "| Glory, glory",
+            "Verse 2",
+            "Em",
+            "Praise him"
+        ]
+    );
+}
+
+#[test]
+fn reads_comment_styles() {
+    let song = Song::from(String::from(
+        "{c: Plain}\n{ci: Softly}\n{cb: Twice}\n{highlight: Key change}\n",
+    ));
+    let strings: Vec<&SongString> = song
+        .content
+        .iter()
+        .map(|line| &line.blocks[0].0[0])
+        .collect();
+    assert!(matches!(strings[0], SongString::Comment(text) if text == "Plain"));
+    assert!(matches!(strings[1], SongString::CommentItalic(text) if text == "Softly"));
+    assert!(matches!(strings[2], SongString::CommentBox(text) if text == "Twice"));
+    assert!(matches!(strings[3], SongString::Highlight(text) if text == "Key "));
+    assert!(strings.iter().all(|string| string.is_comment()));
+    assert_eq!(rows(&song.content[2]), vec!["[Twice]"]);
+}
+
+#[test]
+fn reads_annotations_above_lyrics() {
+    let song = Song::in_key(
+        String::from("{key: G}\n[*Riff]Walking [G]down the [C]road\n"),
+        "A".parse().unwrap(),
+    );
+    assert_eq!(song.chords(), vec!["A", "D"]);
+    assert_eq!(
+        rows(&song.content[0]),
+        vec!["Riff    A        D", "Walking down the road"]
+    );
+}
+
+const SELECTORS: &str = "{c-guitar: Capo 2}\n{c-!guitar: Play in A}\n\
+                         {start_of_chorus-keys}\n[A]Keys only\n{end_of_chorus}\n\
+                         {c-alice: Alice sings}\n[A]Everyone\n{c-guitar!: Not for guitar}\n\
+                         {capo-keys: 5}\n";
+
+fn selected(instrument: Option<&str>, user: Option<&str>) -> Vec<String> {
+    let config = Config {
+        instrument: instrument.map(str::to_owned),
+        user: user.map(str::to_owned),
+        ..Default::default()
+    };
+    Song::from(SELECTORS.to_owned())
+        .display(0, &config)
+        .iter()
+        .flat_map(rows)
+        .collect()
+}
+
+#[test]
+fn selects_content_for_instruments_and_users() {
+    assert_eq!(
+        selected(Some("guitar"), None),
+        vec!["Capo 2", "A", "Everyone"]
+    );
+    assert_eq!(
+        selected(Some("keys"), Some("Alice")),
+        vec![
+            "Play in A",
+            "| A",
+            "| Keys only",
+            "Alice sings",
+            "A",
+            "Everyone",
+            "Not for guitar"
+        ]
+    );
+}
+
+#[test]
+fn applies_directives_for_instruments_and_users() {
+    assert_eq!(Song::from(SELECTORS.to_owned()).capo, 0);
+    let keys = Selection {
+        instrument: Some(String::from("keys")),
+        user: None,
+    };
+    assert_eq!(Song::parse(SELECTORS.to_owned(), None, &keys).capo, 5);
+    let guitar = Selection {
+        instrument: Some(String::from("guitar")),
+        user: None,
+    };
+    assert_eq!(Song::parse(SELECTORS.to_owned(), None, &guitar).capo, 0);
+}
+
+#[test]
+fn reads_hyphenated_directives() {
+    let song = Song::from(String::from(
+        "{key: C}\n{Capo-Bass_Guitar: 2}\n[C]Down [G]low\n",
+    ));
+    assert_eq!(song.key, Some("Bb".parse().unwrap()));
+    assert_eq!(song.chords(), vec!["Bb", "F"]);
+}
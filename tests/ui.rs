@@ -0,0 +1,234 @@
+use common::TempDir;
+use gpro::{
+    app::{App, AppState},
+    conf::{Config, Icons},
+    parser::Song,
+    ui,
+};
+use std::fs;
+use tui::{backend::TestBackend, buffer::Buffer, layout::Rect, Terminal};
+
+mod common;
+
+const SONG: &str = "{title: Amazing Grace}\n{subtitle: John Newton}\n{key: G}\n\
+                    A[G]mazing [C]grace\n\
+                    {soc}\n[D]I once was [G]lost\n{eoc}\n";
+
+/// Creates an app for a library folder with one song and one playlist, removed with the directory
+fn app(name: &str) -> (App, TempDir) {
+    let dir = TempDir::new(&format!("ui-{}", name));
+    let path = dir.path().join("Songs");
+    fs::create_dir_all(&path).unwrap();
+    fs::write(path.join("grace.txt"), SONG).unwrap();
+    fs::write(
+        path.join("set.lst"),
+        "Sunday\nAmazing Grace - John Newton\n",
+    )
+    .unwrap();
+    let app = App::new(Config {
+        path,
+        icons: Icons {
+            folder: String::from("+ "),
+            playlist: String::from("= "),
+            song: String::from("- "),
+        },
+        ..Config::default()
+    });
+    (app, dir)
+}
+
+/// The rows of a buffer without styles or trailing spaces
+fn rows(buffer: &Buffer) -> Vec<String> {
+    let width = buffer.area.width as usize;
+    buffer
+        .content
+        .chunks(width.max(1))
+        .map(|row| {
+            let row: String = row.iter().map(|cell| cell.symbol.as_str()).collect();
+            row.trim_end().to_owned()
+        })
+        .collect()
+}
+
+fn render(app: &mut App, width: u16, height: u16) -> Vec<String> {
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    terminal.draw(|f| ui::draw(f, app)).unwrap();
+    rows(terminal.backend().buffer())
+}
+
+#[test]
+fn draws_song_with_chorus_bars() {
+    let (mut app, _dir) = app("chorus");
+    app.song = Some(Song::from(SONG.to_owned()));
+    assert_eq!(
+        render(&mut app, 60, 10),
+        vec![
+            "",
+            " ┌Songs─────────────┐┌Amazing Grace - John Newton─────────┐",
+            " │- Amazing Grace - ││ G      C                           │",
+            " │= Sunday          ││Amazing grace                       │",
+            " │                  ││| D          G                      │",
+            " │                  ││| I once was lost                   │",
+            " │                  ││                                    │",
+            " │                  ││                                    │",
+            " └──────────────────┘└────────────────────────────────────┘",
+            "",
+        ]
+    );
+}
+
+#[test]
+fn fills_words_under_long_chords_with_hyphens() {
+    let (mut app, _dir) = app("hyphens");
+    app.song = Some(Song::from(String::from(
+        "{title: Hyphens}\nA[Gmaj7]ma[C]zing gr[Dsus4]a[G]ce, [Em]\n",
+    )));
+    assert_eq!(
+        render(&mut app, 60, 6),
+        vec![
+            "",
+            " ┌Songs─────────────┐┌Hyphens─────────────────────────────┐",
+            " │- Amazing Grace - ││ Gmaj7 C      Dsus4 G   Em          │",
+            " │= Sunday          ││Ama----zing gra-----ce,             │",
+            " └──────────────────┘└────────────────────────────────────┘",
+            "",
+        ]
+    );
+}
+
+#[test]
+fn wraps_lines_into_columns() {
+    let (mut app, _dir) = app("columns");
+    let lines: String = (1..=8).map(|i| format!("[C]Line {}\n", i)).collect();
+    app.song = Some(Song::from(format!("{{title: Columns}}\n{}", lines)));
+    assert_eq!(
+        render(&mut app, 60, 10),
+        vec![
+            "",
+            " ┌Songs─────────────┐┌Columns─────────────────────────────┐",
+            " │- Amazing Grace - ││C       C       C                   │",
+            " │= Sunday          ││Line 1  Line 4  Line 7              │",
+            " │                  ││C       C       C                   │",
+            " │                  ││Line 2  Line 5  Line 8              │",
+            " │                  ││C       C                           │",
+            " │                  ││Line 3  Line 6                      │",
+            " └──────────────────┘└────────────────────────────────────┘",
+            "",
+        ]
+    );
+}
+
+#[test]
+fn draws_end_of_search_input() {
+    let (mut app, _dir) = app("search");
+    app.state = AppState::Searching;
+    app.input = String::from("amazing grâce über alles");
+    app.search();
+    assert_eq!(
+        render(&mut app, 60, 8)
+            .iter()
+            .map(|row| row.chars().take(21).collect::<String>())
+            .collect::<Vec<String>>(),
+        vec![
+            "",
+            " ┌Search────────────┐",
+            " │                  │",
+            " └──────────────────┘",
+            " ┌Search────────────┐",
+            " │ grâce über alles|│",
+            " └──────────────────┘",
+            "",
+        ]
+    );
+}
+
+#[test]
+fn draws_key_picker() {
+    let (mut app, _dir) = app("keys");
+    app.song = Some(Song::from(SONG.to_owned()));
+    app.open_key_picker();
+    assert_eq!(
+        render(&mut app, 60, 18)[4..17],
+        [
+            " │ C/Am             ││| D          G                      │",
+            " │ Db/Bbm capo 1: C ││| I once was lost                   │",
+            " │ D/Bm             ││                                    │",
+            " │ Eb/Cm  capo 1: D ││                                    │",
+            " │ E/C#m            ││                                    │",
+            " │ F/Dm   capo 1: E ││                                    │",
+            " │ F#/D#m capo 2: E ││                                    │",
+            " │*G/Em             ││                                    │",
+            " │ Ab/Fm  capo 1: G ││                                    │",
+            " │ A/F#m            ││                                    │",
+            " │ Bb/Gm  capo 1: A ││                                    │",
+            " │ B/G#m  capo 2: A ││                                    │",
+            " └──────────────────┘└────────────────────────────────────┘",
+        ]
+    );
+}
+
+#[test]
+fn draws_in_tiny_terminals() {
+    let (mut app, _dir) = app("tiny");
+    app.song = Some(Song::from(SONG.to_owned()));
+    for state in [
+        AppState::Default,
+        AppState::Searching,
+        AppState::Transposing,
+        AppState::Importing,
+    ] {
+        app.state = state;
+        app.input = String::from("grâce");
+        for (width, height) in [(0, 0), (1, 1), (3, 3), (5, 4), (24, 2), (24, 4), (60, 3)] {
+            render(&mut app, width, height);
+        }
+    }
+}
+
+#[test]
+fn wraps_lines_in_short_containers() {
+    let song = Song::from(SONG.to_owned());
+    for height in 0..4 {
+        let columns = ui::wrap_lines(&song.content, Rect::new(0, 0, 40, height), 0, 2);
+        let lines: usize = columns.iter().map(|column| column.lines().len()).sum();
+        assert_eq!(lines, song.content.len());
+    }
+}
+
+/// The number of lines in each column a song is divided into
+fn column_lengths(chordpro: &str, width: u16, height: u16) -> Vec<usize> {
+    let song = Song::from(chordpro.to_owned());
+    ui::wrap_lines(&song.content, Rect::new(0, 0, width, height), 0, 2)
+        .iter()
+        .map(|column| column.lines().len())
+        .collect()
+}
+
+#[test]
+fn balances_column_heights() {
+    let lines: String = (1..=6).map(|i| format!("[C]Line {}\n", i)).collect();
+    assert_eq!(column_lengths(&lines, 60, 10), vec![3, 3]);
+}
+
+#[test]
+fn keeps_sections_in_one_column() {
+    let song = "[C]One\n[C]Two\n[C]Three\n\n[G]Four\n[G]Five\n[G]Six\n\n[D]Seven\n[D]Eight\n";
+    assert_eq!(column_lengths(song, 60, 10), vec![4, 4, 2]);
+    // Even when the columns run off the screen
+    assert_eq!(column_lengths(song, 16, 10), vec![4, 4, 2]);
+}
+
+#[test]
+fn splits_sections_taller_than_the_screen() {
+    let lines: String = (1..=6).map(|i| format!("[C]Line {}\n", i)).collect();
+    assert_eq!(
+        column_lengths(&format!("[G]Intro\n\n{}", lines), 60, 8),
+        vec![2, 3, 3]
+    );
+}
+
+#[test]
+fn breaks_columns_where_asked() {
+    let song = "[C]One\n{column_break}\n[C]Two\n{colb}\n\n[C]Three\n{new_page}\n[C]Four\n";
+    assert_eq!(column_lengths(song, 60, 20), vec![1, 2, 1, 1]);
+}
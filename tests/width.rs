@@ -0,0 +1,134 @@
+use gpro::{
+    conf::{Config, Theme},
+    export,
+    import::{self, Format},
+    parser::{display_width, Song, SongLine},
+};
+
+/// The rows a line is shown as, without styles
+fn rows(line: &SongLine) -> Vec<String> {
+    line.format(&Theme::default())
+        .iter()
+        .map(|spans| {
+            spans
+                .0
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect::<String>()
+        })
+        .collect()
+}
+
+/// The column at which a piece of a row starts
+fn column(row: &str, piece: &str) -> usize {
+    display_width(&row[..row.find(piece).unwrap()])
+}
+
+fn line(chordpro: &str) -> SongLine {
+    Song::from(chordpro.to_owned()).content.remove(0)
+}
+
+#[test]
+fn measures_display_width() {
+    assert_eq!(display_width("Amazing"), 7);
+    assert_eq!(display_width("아리랑"), 6);
+    assert_eq!(display_width("さくら"), 6);
+    // An e followed by a combining acute accent
+    assert_eq!(display_width("e\u{301}e\u{301}n"), 3);
+    assert_eq!(display_width("🎸!"), 3);
+}
+
+#[test]
+fn aligns_chords_over_korean() {
+    let line = line("[C]아리[G]랑 아리[F]랑 [C]아라리요");
+    let rows = rows(&line);
+    assert_eq!(rows[1], "아리랑 아리랑 아라리요");
+    assert_eq!(column(&rows[0], "G"), column(&rows[1], "랑"));
+    assert_eq!(column(&rows[0], "F"), 11);
+    assert_eq!(column(&rows[0], "C "), 0);
+    assert_eq!(line.width(), display_width(&rows[1]));
+}
+
+#[test]
+fn aligns_chords_over_japanese() {
+    let line = line("[Am]さくら [Dm]さくら [E7]やよいの[Am]空は");
+    let rows = rows(&line);
+    assert_eq!(rows[1], "さくら さくら やよいの空は");
+    assert_eq!(column(&rows[0], "Dm"), 7);
+    assert_eq!(column(&rows[0], "E7"), 14);
+    assert_eq!(column(&rows[1], "空"), 22);
+    assert_eq!(display_width(&rows[0][..rows[0].rfind("Am").unwrap()]), 22);
+}
+
+#[test]
+fn aligns_chords_over_accents() {
+    // Dutch with precomposed and combining accents
+    let line = line("[G]Één, [D]twe\u{301}e, [Em]drie\u{308}n [C]café");
+    let rows = rows(&line);
+    assert_eq!(rows[1], "Één, twe\u{301}e, drie\u{308}n café");
+    assert_eq!(column(&rows[0], "D"), 5);
+    assert_eq!(column(&rows[0], "Em"), 11);
+    assert_eq!(column(&rows[0], "C"), 17);
+    assert_eq!(column(&rows[1], "café"), 17);
+}
+
+#[test]
+fn fills_wide_words_under_long_chords() {
+    let line = line("[Cmaj7]아[Am7]리랑");
+    let rows = rows(&line);
+    assert_eq!(rows[0], "Cmaj7 Am7 ");
+    assert_eq!(rows[1], "아----리랑");
+}
+
+#[test]
+fn wraps_by_display_width() {
+    let line = line("[C]아리랑 [G]아리랑 [F]아라리요 [C]아리랑 [G]고개로 [C]넘어간다");
+    for wrapped in line.wrap(16) {
+        assert!(wrapped.width() <= 16);
+    }
+}
+
+#[test]
+fn imports_chords_over_wide_lyrics() {
+    let chordpro = import::import("C      G\n아리랑 아리랑\n".as_bytes(), Format::Text).unwrap();
+    assert!(chordpro.contains("[C]아리랑 [G]아리랑"));
+    let chordpro = import::import("C   G\n아리랑\n".as_bytes(), Format::Text).unwrap();
+    assert!(chordpro.contains("[C]아리[G]랑"));
+}
+
+/// The position and text of every piece of text drawn in a PDF
+fn pdf_text(pdf: &[u8]) -> Vec<(f32, f32, String)> {
+    let pdf = String::from_utf8_lossy(pdf);
+    let lines: Vec<&str> = pdf.lines().collect();
+    lines
+        .windows(2)
+        .filter_map(|pair| {
+            let position: Vec<f32> = pair[0]
+                .strip_suffix(" Td")?
+                .split(' ')
+                .map(|number| number.parse().unwrap())
+                .collect();
+            let text = pair[1].strip_suffix(" Tj")?;
+            Some((position[0], position[1], text.to_owned()))
+        })
+        .collect()
+}
+
+#[test]
+fn aligns_chords_in_pdf_exports() {
+    let song = Song::from(String::from(
+        "{title: Arirang}\n[C]아리[G]랑 [Am]e\u{301}te [F]x\n",
+    ));
+    let pdf = export::export(&[song], None, export::Format::Pdf, 80, &Config::default());
+    let text = pdf_text(&pdf);
+    let x = |wanted: &str| {
+        text.iter()
+            .find(|(_, _, text)| text == wanted)
+            .map(|(x, ..)| *x)
+            .unwrap()
+    };
+    // Wide characters take up two columns and combining accents none
+    assert!((x("(Am )") - x("(ete )")).abs() < 0.01);
+    assert!((x("(G )") - x("(?  )")).abs() < 0.01);
+    assert_eq!(x("(? ? )"), x("(C )"));
+}